@@ -0,0 +1,3 @@
+ALTER TABLE solana_transactions
+  DROP COLUMN send_attempts,
+  DROP COLUMN last_resent_at;
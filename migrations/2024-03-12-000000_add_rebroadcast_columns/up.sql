@@ -0,0 +1,3 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN send_attempts INT UNSIGNED NOT NULL DEFAULT 0,
+  ADD COLUMN last_resent_at DATETIME(3);
@@ -0,0 +1,3 @@
+ALTER TABLE solana_transactions
+  DROP COLUMN tx_error,
+  DROP COLUMN failed_instruction_index;
@@ -0,0 +1,3 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN tx_error varchar(1000),
+  ADD COLUMN failed_instruction_index TINYINT UNSIGNED;
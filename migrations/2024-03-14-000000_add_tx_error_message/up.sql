@@ -0,0 +1,2 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN tx_error_message varchar(1000);
@@ -0,0 +1,3 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN owner varchar(44) NOT NULL DEFAULT '',
+  ADD INDEX solana_transactions_owner_idx (owner, id);
@@ -0,0 +1,2 @@
+ALTER TABLE solana_transactions
+  DROP COLUMN tx_type;
@@ -0,0 +1,4 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN tx_type varchar(32) NOT NULL DEFAULT '';
+-- Initialize was the only transaction built before tx_type was recorded.
+UPDATE solana_transactions SET tx_type = 'initialize' WHERE tx_type = '';
@@ -0,0 +1,3 @@
+ALTER TABLE users
+  DROP INDEX users_pubkey_idx,
+  DROP COLUMN pubkey;
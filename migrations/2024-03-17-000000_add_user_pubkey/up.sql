@@ -0,0 +1,3 @@
+ALTER TABLE users
+  ADD COLUMN pubkey varchar(44) NULL,
+  ADD UNIQUE INDEX users_pubkey_idx (pubkey);
@@ -0,0 +1,4 @@
+ALTER TABLE solana_transactions
+  DROP INDEX solana_transactions_tx_type_idx,
+  DROP COLUMN params,
+  DROP COLUMN program_id;
@@ -0,0 +1,7 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN program_id varchar(44) NOT NULL DEFAULT '',
+  ADD COLUMN params JSON NULL,
+  ADD INDEX solana_transactions_tx_type_idx (tx_type);
+-- Initialize, the only transaction built before program_id was recorded,
+-- calls the t-vault program (its declare_id!).
+UPDATE solana_transactions SET program_id = 'Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS' WHERE tx_type = 'initialize';
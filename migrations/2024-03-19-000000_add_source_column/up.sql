@@ -0,0 +1,2 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN source varchar(16) NOT NULL DEFAULT '';
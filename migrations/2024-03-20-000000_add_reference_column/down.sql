@@ -0,0 +1,3 @@
+ALTER TABLE solana_transactions
+  DROP INDEX solana_transactions_reference_idx,
+  DROP COLUMN reference;
@@ -0,0 +1,3 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN reference varchar(44) NULL,
+  ADD INDEX solana_transactions_reference_idx (reference);
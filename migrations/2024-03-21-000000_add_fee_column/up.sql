@@ -0,0 +1,2 @@
+ALTER TABLE solana_transactions
+  ADD COLUMN fee bigint unsigned NULL;
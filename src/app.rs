@@ -0,0 +1,1506 @@
+use std::{convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+
+use askama::Template;
+use askama_axum::{IntoResponse, Response};
+use axum::{
+    extract::{ConnectInfo, Path, Query},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{get, post},
+    Extension, Form, Json, Router,
+};
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, Utc};
+use deadpool_diesel::mysql::Pool;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::{
+    commitment_config::CommitmentLevel,
+    pubkey::Pubkey,
+    signature::Signature,
+};
+use futures_util::stream;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tower_http::cors::{Any, CorsLayer};
+
+use crate::blockhash_cache::BlockhashCache;
+use crate::config::Config;
+use crate::instruction_decoder::{decode_message, DecodedInstruction};
+use crate::program_errors::describe_preflight_failure;
+use crate::rate_limit::RateLimiter;
+use crate::repository::{self, UserDb};
+use crate::repository_error::RepositoryError;
+use crate::rpc_pool::RpcPool;
+use crate::session::{SessionConfig, WalletSession};
+use crate::signature_subscriber::SignatureSubscriber;
+use crate::solana_rpc::SolanaRpc;
+use crate::solana_transactions_repository::{SolanaTransaction, TransactionFilter, TxTypeStats};
+use crate::status_events::TxStatusSender;
+use crate::transaction_builder::{build_transaction, BuildTransactionError, TxSource, TxType};
+use crate::transaction_preview::{preview_transaction, TxPreview};
+use crate::transaction_status::TransactionStatus;
+use crate::transaction_validation::{
+    decode_transaction, signed_by_fee_payer, validate_submitted_transaction, TxValidationError,
+};
+
+/// Most rows a transaction history page returns.
+const MAX_HISTORY_PAGE_SIZE: i64 = 100;
+
+const DEFAULT_HISTORY_PAGE_SIZE: i64 = 25;
+
+/// Transactions one address may have built per minute without signing in,
+/// since each one is a row the tracker looks up.
+const UNAUTHENTICATED_BUILDS_PER_MINUTE: usize = 10;
+
+/// What the handlers share, each handed to them as an `Extension`.
+pub struct AppState {
+    pub database_pool: Arc<Pool>,
+    pub rpc_client: Arc<dyn SolanaRpc>,
+    pub rpc_pool: Arc<RpcPool>,
+    pub blockhash_cache: Arc<BlockhashCache>,
+    pub session_config: SessionConfig,
+    pub signature_subscriber: SignatureSubscriber,
+    pub status_events: TxStatusSender,
+    pub config: Arc<Config>,
+}
+
+/// Every route the server answers.
+pub fn app(state: AppState) -> Router {
+    // Solana Actions must be reachable cross-origin by blink clients.
+    let actions_router = Router::new()
+        .route("/actions.json", get(handle_get_actions_json))
+        .route(
+            "/api/actions/:tx_type",
+            get(handle_get_action).post(handle_post_action),
+        )
+        .route("/api/actions/:tx_type/next", post(handle_post_action_next))
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::OPTIONS])
+                .allow_headers([
+                    header::CONTENT_TYPE,
+                    header::AUTHORIZATION,
+                    header::CONTENT_ENCODING,
+                    header::ACCEPT_ENCODING,
+                ])
+                .expose_headers([
+                    header::HeaderName::from_static("x-action-version"),
+                    header::HeaderName::from_static("x-blockchain-ids"),
+                ]),
+        );
+
+    Router::new()
+        // No Auth
+        .route("/styles.css", get(styles))
+        .route("/script.js", get(script))
+        .route("/icon.svg", get(icon))
+        .route("/", get(index))
+        .route("/tx-modal", get(handle_get_tx_modal))
+        .route("/tx-status", get(handle_get_tx_status))
+        .route("/tx-submit", post(handle_submit_tx))
+        .route("/tx-status-data", get(handle_get_tx_status_data))
+        .route("/tx-status-stream", get(handle_get_tx_status_stream))
+        .route("/tx-audit", get(handle_get_tx_audit))
+        .route("/rpc-stats", get(handle_get_rpc_stats))
+        .route("/history", get(handle_get_history))
+        .route("/history/rows", get(handle_get_history_rows))
+        .route("/api/history", get(handle_get_history_json))
+        .route("/api/tx-stats", get(handle_get_tx_stats))
+        .route("/auth/challenge", get(handle_get_auth_challenge))
+        .route("/auth/verify", post(handle_post_auth_verify))
+        .route("/auth/sign-out", post(handle_post_auth_sign_out))
+        .route("/profile", get(handle_get_profile).post(handle_post_profile))
+        .route("/profile/edit", get(handle_get_profile_edit))
+        .route(
+            "/solana-pay/:tx_type",
+            get(handle_get_solana_pay).post(handle_post_solana_pay),
+        )
+        .route("/solana-pay/:tx_type/qr", get(handle_get_solana_pay_qr))
+        .merge(actions_router)
+        .layer(Extension(state.database_pool))
+        .layer(Extension(state.rpc_client))
+        .layer(Extension(state.rpc_pool))
+        .layer(Extension(state.blockhash_cache))
+        .layer(Extension(state.session_config))
+        .layer(Extension(state.signature_subscriber))
+        .layer(Extension(state.status_events))
+        .layer(Extension(state.config))
+        .layer(Extension(Arc::new(RateLimiter::new(
+            UNAUTHENTICATED_BUILDS_PER_MINUTE,
+            Duration::from_secs(60),
+        ))))
+}
+
+#[derive(Template)]
+#[template(path = "index.html")]
+struct IndexTemplate;
+
+async fn index() -> impl IntoResponse {
+    IndexTemplate
+}
+
+async fn styles() -> impl IntoResponse {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/css")
+        .body(include_str!("../templates/styles.css").to_owned())
+        .unwrap()
+}
+
+async fn script() -> impl IntoResponse {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/javascript")
+        .body(include_str!("../templates/script.js").to_owned())
+        .unwrap()
+}
+
+async fn icon() -> impl IntoResponse {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "image/svg+xml")
+        .body(include_str!("../templates/icon.svg").to_owned())
+        .unwrap()
+}
+
+#[derive(Template)]
+#[template(path = "tx-modal.html")]
+struct TxModalTemplate {
+    tx_id: i32,
+    transaction_name: String,
+    button_id: String,
+    encoded_tx: String,
+    preview: TxPreview,
+    instructions: Vec<DecodedInstruction>,
+}
+
+#[derive(Deserialize)]
+struct TxModalQueryParams {
+    tx_type: String,
+}
+
+/// Status code a handler answers with when the repository fails.
+fn repository_error_status(err: &RepositoryError) -> StatusCode {
+    match err {
+        RepositoryError::NotFound => StatusCode::NOT_FOUND,
+        RepositoryError::Conflict(_) | RepositoryError::InvalidTransition { .. } => {
+            StatusCode::CONFLICT
+        }
+        RepositoryError::Pool(_) | RepositoryError::Interact(_) => StatusCode::SERVICE_UNAVAILABLE,
+        RepositoryError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn build_error_status(err: &BuildTransactionError) -> StatusCode {
+    match err {
+        BuildTransactionError::Database(err) => repository_error_status(err),
+        BuildTransactionError::Blockhash => StatusCode::BAD_GATEWAY,
+        BuildTransactionError::Serialize => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthChallengeQueryParams {
+    pubkey: String,
+}
+
+#[derive(Serialize)]
+struct AuthChallengeResponse {
+    message: String,
+}
+
+/// Starts Sign-In With Solana: returns the message for the wallet to sign
+/// and keeps the challenge in an encrypted cookie.
+async fn handle_get_auth_challenge(
+    Query(query_params): Query<AuthChallengeQueryParams>,
+    Extension(session_config): Extension<SessionConfig>,
+) -> Response {
+    let pubkey = match Pubkey::from_str(&query_params.pubkey) {
+        Ok(pubkey) => pubkey,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid pubkey").into_response(),
+    };
+    let (jar, message) = session_config.issue_challenge(&pubkey);
+    (jar, Json(AuthChallengeResponse { message })).into_response()
+}
+
+#[derive(Deserialize)]
+struct AuthVerifyPayload {
+    pubkey: String,
+    /// Base64 ed25519 signature over the challenge message.
+    signature: String,
+}
+
+/// Signs the wallet in, creating its profile on first sign-in.
+async fn handle_post_auth_verify(
+    headers: HeaderMap,
+    Extension(session_config): Extension<SessionConfig>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Json(payload): Json<AuthVerifyPayload>,
+) -> Response {
+    let pubkey = match Pubkey::from_str(&payload.pubkey) {
+        Ok(pubkey) => pubkey,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid pubkey").into_response(),
+    };
+    let jar = session_config.jar(&headers);
+    let jar = match session_config.verify_challenge(jar, &pubkey, &payload.signature) {
+        Ok(jar) => jar,
+        Err(err) => return (StatusCode::UNAUTHORIZED, err.to_string()).into_response(),
+    };
+
+    if let Err(err) = repository::get_or_create_by_pubkey(
+        &database_pool,
+        pubkey.to_string(),
+        default_profile_name(&pubkey),
+    )
+    .await
+    {
+        println!("Failed to load profile for {}: {}", pubkey, err);
+        return (repository_error_status(&err), err.to_string()).into_response();
+    }
+    (jar, StatusCode::NO_CONTENT).into_response()
+}
+
+async fn handle_post_auth_sign_out(
+    headers: HeaderMap,
+    Extension(session_config): Extension<SessionConfig>,
+) -> Response {
+    let jar = session_config.sign_out(session_config.jar(&headers));
+    (jar, StatusCode::NO_CONTENT).into_response()
+}
+
+/// Longest profile name accepted from the edit form.
+const MAX_PROFILE_NAME_LENGTH: usize = 64;
+
+const MAX_PROFILE_AGE: i32 = 150;
+
+/// Name given to a new profile: the pubkey shortened to `Abcd…wxyz`.
+fn default_profile_name(pubkey: &Pubkey) -> String {
+    let pubkey = pubkey.to_string();
+    format!("{}…{}", &pubkey[..4], &pubkey[pubkey.len() - 4..])
+}
+
+#[derive(Template)]
+#[template(path = "profile.html")]
+struct ProfileTemplate {
+    profile: UserDb,
+}
+
+#[derive(Template)]
+#[template(path = "profile-edit.html")]
+struct ProfileEditTemplate {
+    profile: UserDb,
+    error: Option<String>,
+}
+
+async fn load_profile(database_pool: &Pool, session: &WalletSession) -> Result<UserDb, Response> {
+    repository::get_or_create_by_pubkey(
+        database_pool,
+        session.pubkey.to_string(),
+        default_profile_name(&session.pubkey),
+    )
+    .await
+    .map_err(|err| (repository_error_status(&err), err.to_string()).into_response())
+}
+
+async fn handle_get_profile(
+    session: WalletSession,
+    Extension(database_pool): Extension<Arc<Pool>>,
+) -> Response {
+    match load_profile(&database_pool, &session).await {
+        Ok(profile) => ProfileTemplate { profile }.into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn handle_get_profile_edit(
+    session: WalletSession,
+    Extension(database_pool): Extension<Arc<Pool>>,
+) -> Response {
+    match load_profile(&database_pool, &session).await {
+        Ok(profile) => ProfileEditTemplate {
+            profile,
+            error: None,
+        }
+        .into_response(),
+        Err(response) => response,
+    }
+}
+
+#[derive(Deserialize)]
+struct ProfilePayload {
+    name: String,
+    /// Empty when the field is cleared.
+    age: String,
+}
+
+impl ProfilePayload {
+    fn validate(&self) -> Result<(String, Option<i32>), &'static str> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Name is required");
+        }
+        if name.chars().count() > MAX_PROFILE_NAME_LENGTH {
+            return Err("Name is too long");
+        }
+        let age = match self.age.trim() {
+            "" => None,
+            age => match age.parse::<i32>() {
+                Ok(age) if (0..=MAX_PROFILE_AGE).contains(&age) => Some(age),
+                _ => return Err("Invalid age"),
+            },
+        };
+        Ok((name.to_string(), age))
+    }
+}
+
+/// Saves the profile form, showing it again with the error if it is invalid.
+async fn handle_post_profile(
+    session: WalletSession,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Form(payload): Form<ProfilePayload>,
+) -> Response {
+    let (name, age) = match payload.validate() {
+        Ok(profile) => profile,
+        Err(err) => {
+            return match load_profile(&database_pool, &session).await {
+                // Rendered with 200 so the boosted form swaps it in.
+                Ok(profile) => ProfileEditTemplate {
+                    profile,
+                    error: Some(err.to_string()),
+                }
+                .into_response(),
+                Err(response) => response,
+            };
+        }
+    };
+
+    match repository::update_profile(&database_pool, session.pubkey.to_string(), name, age).await
+    {
+        Ok(profile) => ProfileTemplate { profile }.into_response(),
+        Err(err) => (repository_error_status(&err), err.to_string()).into_response(),
+    }
+}
+
+// Building the tx
+async fn handle_get_tx_modal(
+    session: WalletSession,
+    Query(query_params): Query<TxModalQueryParams>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(rpc_client): Extension<Arc<dyn SolanaRpc>>,
+    Extension(blockhash_cache): Extension<Arc<BlockhashCache>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> impl IntoResponse {
+    let tx_type = TxType::from_str(&query_params.tx_type);
+
+    if let Ok(tx_type) = tx_type {
+        let built_tx = build_transaction(
+            &database_pool,
+            rpc_client.as_ref(),
+            &blockhash_cache,
+            tx_type,
+            &session.pubkey,
+            config.priority_fee_mode,
+            TxSource::Modal,
+        )
+        .await;
+        return match built_tx {
+            Ok(built_tx) => {
+                let tx = decode_transaction(&built_tx.encoded_tx);
+                let preview = match tx.as_ref() {
+                    Some(tx) => preview_transaction(rpc_client.as_ref(), tx).await,
+                    None => TxPreview::default(),
+                };
+                let instructions = tx
+                    .map(|tx| decode_message(&tx.message))
+                    .unwrap_or_default();
+                (
+                    StatusCode::OK,
+                    TxModalTemplate {
+                        tx_id: built_tx.tx_id,
+                        transaction_name: tx_type.name().to_string(),
+                        button_id: tx_type.button_id().to_string(),
+                        encoded_tx: built_tx.encoded_tx,
+                        preview,
+                        instructions,
+                    }
+                    .to_string(),
+                )
+            }
+            Err(err) => (
+                build_error_status(&err),
+                "Failed to build transaction".to_string(),
+            ),
+        };
+    }
+
+    (StatusCode::BAD_REQUEST, "Invalid tx_type".to_string())
+}
+
+// Solana Pay transaction requests
+// https://docs.solanapay.com/spec#specification-transaction-request
+#[derive(Serialize)]
+struct SolanaPayGetResponse {
+    label: String,
+    icon: String,
+}
+
+#[derive(Deserialize)]
+struct SolanaPayPostRequest {
+    account: String,
+}
+
+#[derive(Serialize)]
+struct SolanaPayPostResponse {
+    transaction: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct SolanaPayErrorResponse {
+    message: String,
+}
+
+fn solana_pay_error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(SolanaPayErrorResponse {
+            message: message.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+async fn handle_get_solana_pay(
+    Path(tx_type): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    if TxType::from_str(&tx_type).is_err() {
+        return solana_pay_error(StatusCode::NOT_FOUND, "Invalid tx_type");
+    }
+
+    Json(SolanaPayGetResponse {
+        label: "T-Vault".to_string(),
+        icon: format!("{}/icon.svg", config.public_url),
+    })
+    .into_response()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_post_solana_pay(
+    Path(tx_type): Path<String>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(rpc_client): Extension<Arc<dyn SolanaRpc>>,
+    Extension(blockhash_cache): Extension<Arc<BlockhashCache>>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(rate_limiter): Extension<Arc<RateLimiter>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(payload): Json<SolanaPayPostRequest>,
+) -> Response {
+    if let Some(ConnectInfo(addr)) = connect_info {
+        if !rate_limiter.check(addr.ip()) {
+            return solana_pay_error(StatusCode::TOO_MANY_REQUESTS, "Too many requests");
+        }
+    }
+    let tx_type = match TxType::from_str(&tx_type) {
+        Ok(tx_type) => tx_type,
+        Err(_) => return solana_pay_error(StatusCode::NOT_FOUND, "Invalid tx_type"),
+    };
+    let pubkey = match Pubkey::from_str(&payload.account) {
+        Ok(pubkey) => pubkey,
+        Err(_) => return solana_pay_error(StatusCode::BAD_REQUEST, "Invalid account"),
+    };
+
+    let built_tx = build_transaction(
+        &database_pool,
+        rpc_client.as_ref(),
+        &blockhash_cache,
+        tx_type,
+        &pubkey,
+        config.priority_fee_mode,
+        TxSource::SolanaPay,
+    )
+    .await;
+    match built_tx {
+        Ok(built_tx) => Json(SolanaPayPostResponse {
+            transaction: built_tx.encoded_tx,
+            message: format!("T-Vault {}", tx_type.name()),
+        })
+        .into_response(),
+        Err(err) => solana_pay_error(build_error_status(&err), "Failed to build transaction"),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "solana-pay-qr.html")]
+struct SolanaPayQrTemplate {
+    transaction_name: String,
+    solana_pay_url: String,
+}
+
+async fn handle_get_solana_pay_qr(
+    Path(tx_type): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    if let Ok(tx_type) = TxType::from_str(&tx_type) {
+        return SolanaPayQrTemplate {
+            transaction_name: tx_type.name().to_string(),
+            solana_pay_url: format!(
+                "solana:{}/solana-pay/{}",
+                config.public_url,
+                tx_type.as_str()
+            ),
+        }
+        .into_response();
+    }
+
+    (StatusCode::NOT_FOUND, "Invalid tx_type".to_string()).into_response()
+}
+
+// Solana Actions
+// https://solana.com/docs/advanced/actions
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActionsJson {
+    rules: Vec<ActionRule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActionRule {
+    path_pattern: String,
+    api_path: String,
+}
+
+#[derive(Serialize)]
+struct ActionGetResponse {
+    #[serde(rename = "type")]
+    action_type: String,
+    icon: String,
+    title: String,
+    description: String,
+    label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    links: Option<ActionLinks>,
+}
+
+#[derive(Serialize)]
+struct ActionLinks {
+    actions: Vec<LinkedAction>,
+}
+
+#[derive(Serialize)]
+struct LinkedAction {
+    label: String,
+    href: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    parameters: Vec<ActionParameter>,
+}
+
+#[derive(Serialize)]
+struct ActionParameter {
+    name: String,
+    label: String,
+    required: bool,
+}
+
+#[derive(Deserialize)]
+struct ActionPostRequest {
+    account: String,
+}
+
+#[derive(Serialize)]
+struct ActionPostResponse {
+    #[serde(rename = "type")]
+    response_type: String,
+    transaction: String,
+    message: String,
+    links: ActionPostResponseLinks,
+}
+
+#[derive(Serialize)]
+struct ActionPostResponseLinks {
+    next: NextActionLink,
+}
+
+#[derive(Serialize)]
+struct NextActionLink {
+    #[serde(rename = "type")]
+    link_type: String,
+    href: String,
+}
+
+#[derive(Deserialize)]
+struct NextActionPostRequest {
+    account: String,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct NextActionQueryParams {
+    tx_id: i32,
+}
+
+#[derive(Serialize)]
+struct ActionErrorResponse {
+    message: String,
+}
+
+fn action_response<T: Serialize>(config: &Config, status: StatusCode, body: T) -> Response {
+    let mut response = (status, Json(body)).into_response();
+    let headers = response.headers_mut();
+    headers.insert("X-Action-Version", HeaderValue::from_static("2.1.3"));
+    if let Ok(blockchain_id) = HeaderValue::from_str(&config.actions_blockchain_id) {
+        headers.insert("X-Blockchain-Ids", blockchain_id);
+    }
+    response
+}
+
+fn action_error(config: &Config, status: StatusCode, message: &str) -> Response {
+    action_response(
+        config,
+        status,
+        ActionErrorResponse {
+            message: message.to_string(),
+        },
+    )
+}
+
+async fn handle_get_actions_json(Extension(config): Extension<Arc<Config>>) -> Response {
+    action_response(
+        &config,
+        StatusCode::OK,
+        ActionsJson {
+            rules: vec![ActionRule {
+                path_pattern: "/actions/**".to_string(),
+                api_path: "/api/actions/**".to_string(),
+            }],
+        },
+    )
+}
+
+async fn handle_get_action(
+    Path(tx_type): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    let tx_type = match TxType::from_str(&tx_type) {
+        Ok(tx_type) => tx_type,
+        Err(_) => return action_error(&config, StatusCode::NOT_FOUND, "Invalid tx_type"),
+    };
+
+    let href = format!("{}/api/actions/{}", config.public_url, tx_type.as_str());
+    let query = tx_type
+        .parameters()
+        .iter()
+        .map(|parameter| format!("{0}={{{0}}}", parameter.name))
+        .collect::<Vec<String>>()
+        .join("&");
+    let href = if query.is_empty() {
+        href
+    } else {
+        format!("{}?{}", href, query)
+    };
+
+    action_response(
+        &config,
+        StatusCode::OK,
+        ActionGetResponse {
+            action_type: "action".to_string(),
+            icon: format!("{}/icon.svg", config.public_url),
+            title: format!("T-Vault {}", tx_type.name()),
+            description: tx_type.description().to_string(),
+            label: tx_type.name().to_string(),
+            links: Some(ActionLinks {
+                actions: vec![LinkedAction {
+                    label: tx_type.name().to_string(),
+                    href,
+                    parameters: tx_type
+                        .parameters()
+                        .iter()
+                        .map(|parameter| ActionParameter {
+                            name: parameter.name.to_string(),
+                            label: parameter.label.to_string(),
+                            required: parameter.required,
+                        })
+                        .collect(),
+                }],
+            }),
+        },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_post_action(
+    Path(tx_type): Path<String>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(rpc_client): Extension<Arc<dyn SolanaRpc>>,
+    Extension(blockhash_cache): Extension<Arc<BlockhashCache>>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(rate_limiter): Extension<Arc<RateLimiter>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(payload): Json<ActionPostRequest>,
+) -> Response {
+    if let Some(ConnectInfo(addr)) = connect_info {
+        if !rate_limiter.check(addr.ip()) {
+            return action_error(&config, StatusCode::TOO_MANY_REQUESTS, "Too many requests");
+        }
+    }
+    let tx_type = match TxType::from_str(&tx_type) {
+        Ok(tx_type) => tx_type,
+        Err(_) => return action_error(&config, StatusCode::NOT_FOUND, "Invalid tx_type"),
+    };
+    let pubkey = match Pubkey::from_str(&payload.account) {
+        Ok(pubkey) => pubkey,
+        Err(_) => return action_error(&config, StatusCode::BAD_REQUEST, "Invalid account"),
+    };
+
+    let built_tx = build_transaction(
+        &database_pool,
+        rpc_client.as_ref(),
+        &blockhash_cache,
+        tx_type,
+        &pubkey,
+        config.priority_fee_mode,
+        TxSource::Action,
+    )
+    .await;
+    let built_tx = match built_tx {
+        Ok(built_tx) => built_tx,
+        Err(err) => {
+            return action_error(&config, build_error_status(&err), "Failed to build transaction")
+        }
+    };
+
+    // The wallet broadcasts the transaction itself. The chained `next`
+    // callback reports its signature; the reference lookup covers wallets
+    // that skip it.
+    action_response(
+        &config,
+        StatusCode::OK,
+        ActionPostResponse {
+            response_type: "transaction".to_string(),
+            transaction: built_tx.encoded_tx,
+            message: format!("T-Vault {}", tx_type.name()),
+            links: ActionPostResponseLinks {
+                next: NextActionLink {
+                    link_type: "post".to_string(),
+                    href: format!(
+                        "/api/actions/{}/next?tx_id={}",
+                        tx_type.as_str(),
+                        built_tx.tx_id
+                    ),
+                },
+            },
+        },
+    )
+}
+
+async fn handle_post_action_next(
+    Path(tx_type): Path<String>,
+    Query(query_params): Query<NextActionQueryParams>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(signature_subscriber): Extension<SignatureSubscriber>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(payload): Json<NextActionPostRequest>,
+) -> Response {
+    let tx_type = match TxType::from_str(&tx_type) {
+        Ok(tx_type) => tx_type,
+        Err(_) => return action_error(&config, StatusCode::NOT_FOUND, "Invalid tx_type"),
+    };
+    let (pubkey, signature) = match (
+        Pubkey::from_str(&payload.account),
+        Signature::from_str(&payload.signature),
+    ) {
+        (Ok(pubkey), Ok(signature)) => (pubkey, signature),
+        _ => {
+            return action_error(
+                &config,
+                StatusCode::BAD_REQUEST,
+                "Invalid account or signature",
+            )
+        }
+    };
+
+    let db_tx = match SolanaTransaction::get_by_id(&database_pool, query_params.tx_id).await {
+        Ok(db_tx) => db_tx,
+        Err(RepositoryError::NotFound) => {
+            return action_error(&config, StatusCode::NOT_FOUND, "Invalid tx_id")
+        }
+        Err(err) => return action_error(&config, repository_error_status(&err), &err.to_string()),
+    };
+
+    // Only transactions built by the Actions POST wait on a signature from the wallet.
+    if db_tx.source != TxSource::Action.as_str() || db_tx.tx_type != tx_type.as_str() {
+        return action_error(&config, StatusCode::NOT_FOUND, "Invalid tx_id");
+    }
+    // Only the fee payer the transaction was built for may attach a signature to it.
+    if !db_tx.is_owned_by(&pubkey) {
+        return action_error(&config, StatusCode::FORBIDDEN, "Account does not match tx_id");
+    }
+    // The body is unauthenticated, so the signature has to prove the fee payer
+    // signed the transaction we built.
+    let signed = decode_transaction(&db_tx.tx)
+        .is_some_and(|tx| signature.verify(pubkey.as_ref(), &tx.message_data()));
+    if !signed {
+        return action_error(
+            &config,
+            StatusCode::BAD_REQUEST,
+            "Signature does not match transaction",
+        );
+    }
+
+    let now_utc: DateTime<Utc> = Utc::now();
+    let sent_at = NaiveDateTime::from_timestamp_opt(
+        now_utc.timestamp(),
+        now_utc.timestamp_subsec_millis() * 1_000_000,
+    )
+    .unwrap();
+
+    let db_result = SolanaTransaction::set_status_sent_by_wallet(
+        &database_pool,
+        query_params.tx_id,
+        signature.to_string(),
+        sent_at,
+    )
+    .await;
+    match db_result {
+        Ok(()) => signature_subscriber.subscribe(query_params.tx_id, signature),
+        Err(RepositoryError::Conflict(_) | RepositoryError::InvalidTransition { .. }) => {
+            // The reference lookup may have found the transaction first.
+            let found = SolanaTransaction::get_by_id(&database_pool, query_params.tx_id)
+                .await
+                .is_ok_and(|db_tx| db_tx.tx_signature == Some(signature.to_string()));
+            if !found {
+                return action_error(&config, StatusCode::CONFLICT, "Transaction already submitted");
+            }
+        }
+        Err(err) => return action_error(&config, repository_error_status(&err), &err.to_string()),
+    }
+
+    action_response(
+        &config,
+        StatusCode::OK,
+        ActionGetResponse {
+            action_type: "completed".to_string(),
+            icon: format!("{}/icon.svg", config.public_url),
+            title: format!("T-Vault {}", tx_type.name()),
+            description: format!("Transaction sent: {}", signature),
+            label: "Sent".to_string(),
+            links: None,
+        },
+    )
+}
+
+#[derive(Template)]
+#[template(path = "tx-status.html")]
+struct TxStatusTemplate {
+    tx_signature: String,
+    /// Decoded preflight failure; the transaction was never sent.
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TxStatusQueryParams {
+    tx_signature: String,
+}
+
+async fn handle_get_tx_status(
+    Query(query_params): Query<TxStatusQueryParams>,
+) -> impl IntoResponse {
+    let tx_signature = query_params.tx_signature;
+
+    (
+        StatusCode::OK,
+        TxStatusTemplate {
+            tx_signature,
+            error: None,
+        }
+        .to_string(),
+    )
+}
+
+#[derive(Serialize)]
+struct TxStatusData {
+    tx_id: i32,
+    signature: Option<String>,
+    status: TransactionStatus,
+    error: Option<String>,
+    failed_instruction_index: Option<u8>,
+}
+
+async fn handle_get_tx_status_data(
+    session: WalletSession,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Query(query_params): Query<TxStatusQueryParams>,
+) -> Response {
+    let db_tx = SolanaTransaction::get_by_signature_for_owner(
+        &database_pool,
+        query_params.tx_signature,
+        session.pubkey.to_string(),
+    )
+    .await;
+    let db_tx = match db_tx {
+        Ok(db_tx) => db_tx,
+        Err(RepositoryError::NotFound) => {
+            return (StatusCode::NOT_FOUND, "Transaction not found".to_string()).into_response()
+        }
+        Err(err) => return (repository_error_status(&err), err.to_string()).into_response(),
+    };
+
+    Json(TxStatusData {
+        tx_id: db_tx.id,
+        error: db_tx.error_message(),
+        failed_instruction_index: db_tx.failed_instruction_index,
+        signature: db_tx.tx_signature,
+        status: db_tx.status,
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct HistoryQueryParams {
+    status: Option<String>,
+    tx_type: Option<String>,
+    program_id: Option<String>,
+    /// First day to include, `YYYY-MM-DD`.
+    from: Option<String>,
+    /// Last day to include, `YYYY-MM-DD`.
+    to: Option<String>,
+    cursor: Option<i32>,
+    limit: Option<i64>,
+}
+
+/// History filters, validated so they can be echoed back into page links.
+struct HistoryFilter {
+    status: Option<TransactionStatus>,
+    tx_type: Option<TxType>,
+    program_id: Option<Pubkey>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: i64,
+}
+
+impl HistoryFilter {
+    /// Empty form fields count as unset.
+    fn parse(params: &HistoryQueryParams) -> Result<Self, &'static str> {
+        fn field<T>(value: &Option<String>, parse: impl Fn(&str) -> Option<T>) -> Result<Option<T>, ()> {
+            match value.as_deref().filter(|value| !value.is_empty()) {
+                Some(value) => parse(value).map(Some).ok_or(()),
+                None => Ok(None),
+            }
+        }
+        let date = |value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+
+        Ok(HistoryFilter {
+            status: field(&params.status, |value| TransactionStatus::from_str(value).ok())
+                .map_err(|_| "Invalid status")?,
+            tx_type: field(&params.tx_type, |value| TxType::from_str(value).ok())
+                .map_err(|_| "Invalid tx_type")?,
+            program_id: field(&params.program_id, |value| Pubkey::from_str(value).ok())
+                .map_err(|_| "Invalid program_id")?,
+            from: field(&params.from, date).map_err(|_| "Invalid from date")?,
+            to: field(&params.to, date).map_err(|_| "Invalid to date")?,
+            limit: params
+                .limit
+                .unwrap_or(DEFAULT_HISTORY_PAGE_SIZE)
+                .clamp(1, MAX_HISTORY_PAGE_SIZE),
+        })
+    }
+
+    fn transaction_filter(&self) -> TransactionFilter {
+        TransactionFilter {
+            status: self.status,
+            tx_type: self.tx_type.map(|tx_type| tx_type.as_str().to_string()),
+            program_id: self.program_id.map(|program_id| program_id.to_string()),
+            created_from: self.from.and_then(|from| from.and_hms_opt(0, 0, 0)),
+            created_before: self
+                .to
+                .and_then(|to| to.checked_add_days(Days::new(1)))
+                .and_then(|to| to.and_hms_opt(0, 0, 0)),
+        }
+    }
+
+    /// Query string for the page after `cursor`, keeping these filters.
+    fn next_page_query(&self, cursor: i32) -> String {
+        let mut query = format!("cursor={}&limit={}", cursor, self.limit);
+        if let Some(status) = self.status {
+            query.push_str(&format!("&status={}", status));
+        }
+        if let Some(tx_type) = self.tx_type {
+            query.push_str(&format!("&tx_type={}", tx_type.as_str()));
+        }
+        if let Some(program_id) = self.program_id {
+            query.push_str(&format!("&program_id={}", program_id));
+        }
+        if let Some(from) = self.from {
+            query.push_str(&format!("&from={}", from));
+        }
+        if let Some(to) = self.to {
+            query.push_str(&format!("&to={}", to));
+        }
+        query
+    }
+}
+
+#[derive(Serialize)]
+struct TxHistoryRow {
+    tx_id: i32,
+    tx_type: String,
+    program_id: String,
+    params: Option<serde_json::Value>,
+    status: TransactionStatus,
+    signature: Option<String>,
+    error: Option<String>,
+    created_at: NaiveDateTime,
+    sent_at: Option<NaiveDateTime>,
+    confirmed_at: Option<NaiveDateTime>,
+    finalized_at: Option<NaiveDateTime>,
+    time_to_send: Option<u32>,
+    time_to_confirmed: Option<u32>,
+    time_to_finalized: Option<u32>,
+    /// Compute unit price in micro-lamports.
+    compute_unit_price: Option<u32>,
+    /// Network fee in lamports, quoted when the transaction was built.
+    fee: Option<u64>,
+    explorer_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TxHistoryResponse {
+    transactions: Vec<TxHistoryRow>,
+    next_cursor: Option<i32>,
+}
+
+/// A page of the session wallet's transactions, or the error response to send.
+async fn load_history(
+    database_pool: &Pool,
+    config: &Config,
+    session: &WalletSession,
+    params: &HistoryQueryParams,
+) -> Result<(TxHistoryResponse, HistoryFilter), Response> {
+    let filter = HistoryFilter::parse(params)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()).into_response())?;
+
+    let page = SolanaTransaction::get_page_by_owner(
+        database_pool,
+        session.pubkey.to_string(),
+        filter.transaction_filter(),
+        params.cursor,
+        filter.limit,
+    )
+    .await
+    .map_err(|err| (repository_error_status(&err), err.to_string()).into_response())?;
+
+    let transactions = page
+        .transactions
+        .into_iter()
+        .map(|tx| TxHistoryRow {
+            tx_id: tx.id,
+            error: tx.error_message(),
+            explorer_url: tx
+                .tx_signature
+                .as_ref()
+                .map(|signature| config.explorer_tx_url.replace("{signature}", signature)),
+            tx_type: tx.tx_type,
+            program_id: tx.program_id,
+            params: tx.params,
+            status: tx.status,
+            signature: tx.tx_signature,
+            created_at: tx.created_at,
+            sent_at: tx.sent_at,
+            confirmed_at: tx.confirmed_at,
+            finalized_at: tx.finalized_at,
+            time_to_send: tx.time_to_send,
+            time_to_confirmed: tx.time_to_confirmed,
+            time_to_finalized: tx.time_to_finalized,
+            compute_unit_price: tx.priority_fee,
+            fee: tx.fee,
+        })
+        .collect();
+
+    Ok((
+        TxHistoryResponse {
+            transactions,
+            next_cursor: page.next_cursor,
+        },
+        filter,
+    ))
+}
+
+#[derive(Template)]
+#[template(path = "history.html")]
+struct HistoryTemplate {
+    statuses: &'static [TransactionStatus],
+    tx_types: &'static [TxType],
+    rows: Vec<TxHistoryRow>,
+    next_query: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "history-rows.html")]
+struct HistoryRowsTemplate {
+    rows: Vec<TxHistoryRow>,
+    next_query: Option<String>,
+}
+
+async fn handle_get_history(
+    session: WalletSession,
+    Query(query_params): Query<HistoryQueryParams>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    match load_history(&database_pool, &config, &session, &query_params).await {
+        Ok((history, filter)) => HistoryTemplate {
+            statuses: TransactionStatus::ALL,
+            tx_types: TxType::ALL,
+            rows: history.transactions,
+            next_query: history
+                .next_cursor
+                .map(|cursor| filter.next_page_query(cursor)),
+        }
+        .into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn handle_get_history_rows(
+    session: WalletSession,
+    Query(query_params): Query<HistoryQueryParams>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    match load_history(&database_pool, &config, &session, &query_params).await {
+        Ok((history, filter)) => HistoryRowsTemplate {
+            rows: history.transactions,
+            next_query: history
+                .next_cursor
+                .map(|cursor| filter.next_page_query(cursor)),
+        }
+        .into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn handle_get_history_json(
+    session: WalletSession,
+    Query(query_params): Query<HistoryQueryParams>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    match load_history(&database_pool, &config, &session, &query_params).await {
+        Ok((history, _)) => Json(history).into_response(),
+        Err(response) => response,
+    }
+}
+
+#[derive(Serialize)]
+struct TxTypeStatsResponse {
+    #[serde(flatten)]
+    stats: TxTypeStats,
+    success_rate: Option<f64>,
+}
+
+/// Success rate and confirmation time of each transaction type, across all wallets.
+async fn handle_get_tx_stats(Extension(database_pool): Extension<Arc<Pool>>) -> Response {
+    match SolanaTransaction::get_stats_by_type(&database_pool).await {
+        Ok(stats) => Json(
+            stats
+                .into_iter()
+                .map(|stats| TxTypeStatsResponse {
+                    success_rate: stats.success_rate(),
+                    stats,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(err) => (repository_error_status(&err), err.to_string()).into_response(),
+    }
+}
+
+/// Endpoint health for operators holding `RPC_STATS_TOKEN`.
+async fn handle_get_rpc_stats(
+    headers: HeaderMap,
+    Extension(rpc_pool): Extension<Arc<RpcPool>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    let Some(token) = config.rpc_stats_token.as_deref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|presented| tokens_match(presented, token)) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Json(rpc_pool.stats()).into_response()
+}
+
+/// Compares without stopping at the first differing byte, so the response
+/// time does not give the token away.
+fn tokens_match(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Status line shown on the status page. Failed and Expired read differently
+/// so users can tell a reverted transaction from one that never landed.
+fn status_text(status: TransactionStatus, error: Option<&str>) -> String {
+    match (status, error) {
+        (TransactionStatus::Failed, Some(error)) => format!("Failed: {}", error),
+        (TransactionStatus::Expired, _) => "Expired before landing".to_string(),
+        (status, _) => status.as_str().to_string(),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "tx-audit.html")]
+struct TxAuditTemplate {
+    tx_id: i32,
+    status: TransactionStatus,
+    tx_signature: Option<String>,
+    instructions: Vec<DecodedInstruction>,
+}
+
+#[derive(Deserialize)]
+struct TxAuditQueryParams {
+    tx_id: i32,
+}
+
+// Decodes a stored transaction so what was signed can be audited later.
+async fn handle_get_tx_audit(
+    session: WalletSession,
+    Query(query_params): Query<TxAuditQueryParams>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+) -> Response {
+    let db_tx = SolanaTransaction::get_by_id_for_owner(
+        &database_pool,
+        query_params.tx_id,
+        session.pubkey.to_string(),
+    )
+    .await;
+    let db_tx = match db_tx {
+        Ok(db_tx) => db_tx,
+        Err(RepositoryError::NotFound) => {
+            return (StatusCode::NOT_FOUND, "Transaction not found".to_string()).into_response()
+        }
+        Err(err) => return (repository_error_status(&err), err.to_string()).into_response(),
+    };
+
+    let Some(tx) = decode_transaction(&db_tx.tx) else {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Stored transaction could not be decoded".to_string(),
+        )
+            .into_response();
+    };
+
+    TxAuditTemplate {
+        tx_id: db_tx.id,
+        status: db_tx.status,
+        tx_signature: db_tx.tx_signature,
+        instructions: decode_message(&tx.message),
+    }
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct TxStatusStreamQueryParams {
+    tx_id: Option<i32>,
+    tx_signature: Option<String>,
+}
+
+// Pushes the stored status, then every status change written by the trackers,
+// and ends once the status is final.
+//
+// Without a session the transaction is looked up by signature alone: its
+// status is public on chain, and submits authorised only by the fee payer's
+// signature get no session.
+async fn handle_get_tx_status_stream(
+    session: Option<WalletSession>,
+    Query(query_params): Query<TxStatusStreamQueryParams>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(status_events): Extension<TxStatusSender>,
+) -> Response {
+    // Subscribe before reading the row so no change slips in between.
+    let receiver = status_events.subscribe();
+
+    let owner = session.map(|session| session.pubkey.to_string());
+    let db_tx = match (query_params.tx_id, query_params.tx_signature, owner) {
+        (Some(tx_id), _, Some(owner)) => {
+            SolanaTransaction::get_by_id_for_owner(&database_pool, tx_id, owner).await
+        }
+        (Some(_), None, None) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                "Sign in with your wallet first".to_string(),
+            )
+                .into_response()
+        }
+        (_, Some(tx_signature), Some(owner)) => {
+            SolanaTransaction::get_by_signature_for_owner(&database_pool, tx_signature, owner)
+                .await
+        }
+        (_, Some(tx_signature), None) => {
+            SolanaTransaction::get_by_signature(&database_pool, tx_signature).await
+        }
+        (None, None, _) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Missing tx_id or tx_signature".to_string(),
+            )
+                .into_response()
+        }
+    };
+    let db_tx = match db_tx {
+        Ok(db_tx) => db_tx,
+        Err(RepositoryError::NotFound) => {
+            return (StatusCode::NOT_FOUND, "Transaction not found".to_string()).into_response()
+        }
+        Err(err) => return (repository_error_status(&err), err.to_string()).into_response(),
+    };
+
+    let tx_id = db_tx.id;
+    let current = (
+        db_tx.status,
+        status_text(db_tx.status, db_tx.error_message().as_deref()),
+    );
+    let stream = stream::unfold(
+        (Some(current), BroadcastStream::new(receiver), false),
+        move |(current, mut updates, finished)| async move {
+            if finished {
+                return None;
+            }
+            let (status, text) = match current {
+                Some(current) => current,
+                None => loop {
+                    match updates.next().await? {
+                        Ok(event) if event.tx_id == tx_id => {
+                            break (event.status, status_text(event.status, event.error.as_deref()))
+                        }
+                        _ => continue,
+                    }
+                },
+            };
+            let event = Ok::<_, Infallible>(Event::default().event("status").data(text));
+            Some((event, (None, updates, status.is_terminal())))
+        },
+    );
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubmitTxPayload {
+    tx_id: i32,
+    encoded_serialized_tx: String,
+}
+
+async fn handle_submit_tx(
+    session: Option<WalletSession>,
+    Extension(database_pool): Extension<Arc<Pool>>,
+    Extension(rpc_client): Extension<Arc<dyn SolanaRpc>>,
+    Extension(signature_subscriber): Extension<SignatureSubscriber>,
+    Form(tx_data): Form<SubmitTxPayload>,
+) -> impl IntoResponse {
+    let db_tx = SolanaTransaction::get_by_id(&database_pool, tx_data.tx_id).await;
+
+    let db_tx = match db_tx {
+        Ok(db_tx) => db_tx,
+        Err(RepositoryError::NotFound) => {
+            return (StatusCode::NOT_FOUND, "Invalid associated tx_id".to_string())
+        }
+        Err(err) => return (repository_error_status(&err), err.to_string()),
+    };
+
+    // The owner proves itself with a session or by having signed as fee payer.
+    let owned_by_session = session.is_some_and(|session| db_tx.is_owned_by(&session.pubkey));
+    if !owned_by_session && !signed_by_fee_payer(&tx_data.encoded_serialized_tx, &db_tx.owner) {
+        return (
+            StatusCode::FORBIDDEN,
+            "Transaction belongs to another wallet".to_string(),
+        );
+    }
+
+    let tx = match validate_submitted_transaction(&db_tx, &tx_data.encoded_serialized_tx) {
+        Ok(tx) => tx,
+        Err(TxValidationError::AlreadySubmitted) => {
+            return (
+                StatusCode::CONFLICT,
+                TxValidationError::AlreadySubmitted.to_string(),
+            )
+        }
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()),
+    };
+
+    let send_config = RpcSendTransactionConfig {
+        skip_preflight: false,
+        preflight_commitment: Some(CommitmentLevel::Processed),
+        encoding: None,
+        max_retries: None,
+        min_context_slot: None,
+    };
+
+    let signature_result = rpc_client
+        .send_transaction_with_config(&tx, send_config)
+        .await;
+
+    let now_utc: DateTime<Utc> = Utc::now();
+
+    let sent_at = NaiveDateTime::from_timestamp_opt(
+        now_utc.timestamp(),
+        now_utc.timestamp_subsec_millis() * 1_000_000,
+    )
+    .unwrap();
+
+    if let Ok(signature) = signature_result {
+        let db_result = SolanaTransaction::set_status_sent(
+            &database_pool,
+            tx_data.tx_id,
+            signature.to_string(),
+            sent_at,
+            tx_data.encoded_serialized_tx,
+        )
+        .await;
+        match db_result {
+            Ok(_) => {
+                println!("Successfully updated transaction in db!");
+                signature_subscriber.subscribe(tx_data.tx_id, signature);
+            }
+            Err(err) => println!("Failed to record sent tx {}: {}", tx_data.tx_id, err),
+        }
+        return (
+            StatusCode::OK,
+            TxStatusTemplate {
+                tx_signature: signature.to_string(),
+                error: None,
+            }
+            .to_string(),
+        );
+    }
+
+    if let Err(err) = &signature_result {
+        if let Some(message) = describe_preflight_failure(err, &tx) {
+            return (
+                StatusCode::BAD_REQUEST,
+                TxStatusTemplate {
+                    tx_signature: String::new(),
+                    error: Some(message),
+                }
+                .to_string(),
+            );
+        }
+    }
+
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to submit tx".to_string(),
+    )
+}
+
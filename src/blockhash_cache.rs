@@ -0,0 +1,81 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use solana_client::client_error::Result as ClientResult;
+use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash};
+use tokio::time::sleep;
+
+use crate::solana_rpc::SolanaRpc;
+
+#[derive(Clone, Copy)]
+struct CachedBlockhash {
+    blockhash: Hash,
+    last_valid_block_height: u64,
+    fetched_at: Instant,
+}
+
+/// Latest confirmed blockhash, refreshed in the background so building a
+/// transaction does not wait on an RPC round trip.
+pub struct BlockhashCache {
+    rpc_client: Arc<dyn SolanaRpc>,
+    latest: RwLock<Option<CachedBlockhash>>,
+    max_age: Duration,
+}
+
+impl BlockhashCache {
+    /// Starts refreshing every `refresh_interval`. Reads older than `max_age`
+    /// fetch the blockhash directly instead.
+    pub fn spawn(
+        rpc_client: Arc<dyn SolanaRpc>,
+        refresh_interval: Duration,
+        max_age: Duration,
+    ) -> Arc<Self> {
+        let cache = Arc::new(BlockhashCache {
+            rpc_client,
+            latest: RwLock::new(None),
+            max_age,
+        });
+
+        {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                loop {
+                    if let Err(err) = cache.refresh().await {
+                        println!("Failed to refresh blockhash: {}", err);
+                    }
+                    sleep(refresh_interval).await;
+                }
+            });
+        }
+
+        cache
+    }
+
+    /// The blockhash and its last valid block height, from the cache when it
+    /// is fresh enough.
+    pub async fn get(&self) -> ClientResult<(Hash, u64)> {
+        let cached = *self.latest.read().unwrap();
+        match cached {
+            Some(cached) if cached.fetched_at.elapsed() <= self.max_age => {
+                Ok((cached.blockhash, cached.last_valid_block_height))
+            }
+            _ => {
+                println!("Blockhash cache is stale, fetching directly");
+                self.refresh().await
+            }
+        }
+    }
+
+    async fn refresh(&self) -> ClientResult<(Hash, u64)> {
+        let (blockhash, last_valid_block_height) = self
+            .rpc_client
+            .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+            .await?;
+        *self.latest.write().unwrap() = Some(CachedBlockhash {
+            blockhash,
+            last_valid_block_height,
+            fetched_at: Instant::now(),
+        });
+        Ok((blockhash, last_valid_block_height))
+    }
+}
@@ -0,0 +1,87 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::priority_fee::PriorityFeeMode;
+use crate::rpc_pool::{parse_endpoints, RpcEndpointConfig, RpcRole};
+use crate::signature_subscriber::{websocket_url, TrackingMode};
+
+/// Server settings, read from the environment.
+pub struct Config {
+    pub rpc_endpoints: Vec<RpcEndpointConfig>,
+    pub rpc_send_fanout: bool,
+    pub session_secret: Option<String>,
+    /// Bearer token for `/rpc-stats`. Without one the route is not served.
+    pub rpc_stats_token: Option<String>,
+    pub database_url: String,
+    pub public_url: String,
+    pub actions_blockchain_id: String,
+    pub rebroadcast_interval: Duration,
+    pub rpc_timeout: Duration,
+    pub blockhash_refresh_interval: Duration,
+    pub blockhash_max_age: Duration,
+    pub priority_fee_mode: PriorityFeeMode,
+    pub tracking_mode: TrackingMode,
+    pub ws_url: String,
+    /// Explorer link for a transaction, with `{signature}` replaced.
+    pub explorer_tx_url: String,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let rpc_endpoints = match std::env::var("SOLANA_RPC_ENDPOINTS") {
+            Ok(endpoints) => parse_endpoints(&endpoints).expect("Invalid SOLANA_RPC_ENDPOINTS."),
+            Err(_) => vec![RpcEndpointConfig {
+                url: std::env::var("SOLANA_RPC_URL").expect("SOLANA_RPC_URL not set in env."),
+                role: RpcRole::All,
+                weight: 1,
+            }],
+        };
+        Config {
+            ws_url: std::env::var("SOLANA_WS_URL")
+                .unwrap_or_else(|_| websocket_url(&rpc_endpoints[0].url)),
+            rpc_endpoints,
+            session_secret: std::env::var("SESSION_SECRET").ok(),
+            rpc_stats_token: std::env::var("RPC_STATS_TOKEN").ok(),
+            rpc_send_fanout: std::env::var("RPC_SEND_FANOUT")
+                .map(|fanout| fanout == "true")
+                .unwrap_or(false),
+            database_url: std::env::var("DATABASE_URL").expect("DATABASE_URL not set in env."),
+            public_url: std::env::var("PUBLIC_URL")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            explorer_tx_url: std::env::var("EXPLORER_TX_URL")
+                .unwrap_or_else(|_| "https://explorer.solana.com/tx/{signature}".to_string()),
+            actions_blockchain_id: std::env::var("ACTIONS_BLOCKCHAIN_ID")
+                .unwrap_or_else(|_| "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp".to_string()),
+            rebroadcast_interval: Duration::from_millis(
+                std::env::var("REBROADCAST_INTERVAL_MS")
+                    .ok()
+                    .and_then(|ms| ms.parse().ok())
+                    .unwrap_or(2000),
+            ),
+            rpc_timeout: Duration::from_millis(
+                std::env::var("RPC_TIMEOUT_MS")
+                    .ok()
+                    .and_then(|ms| ms.parse().ok())
+                    .unwrap_or(10_000),
+            ),
+            blockhash_refresh_interval: Duration::from_millis(
+                std::env::var("BLOCKHASH_REFRESH_INTERVAL_MS")
+                    .ok()
+                    .and_then(|ms| ms.parse().ok())
+                    .unwrap_or(400),
+            ),
+            blockhash_max_age: Duration::from_millis(
+                std::env::var("BLOCKHASH_MAX_AGE_MS")
+                    .ok()
+                    .and_then(|ms| ms.parse().ok())
+                    .unwrap_or(2000),
+            ),
+            priority_fee_mode: std::env::var("PRIORITY_FEE_MODE")
+                .map(|mode| PriorityFeeMode::from_str(&mode).expect("Invalid PRIORITY_FEE_MODE."))
+                .unwrap_or(PriorityFeeMode::Off),
+            tracking_mode: std::env::var("TRACKING_MODE")
+                .map(|mode| TrackingMode::from_str(&mode).expect("Invalid TRACKING_MODE."))
+                .unwrap_or(TrackingMode::Polling),
+        }
+    }
+}
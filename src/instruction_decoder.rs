@@ -0,0 +1,173 @@
+use anchor_client::anchor_lang::{AnchorDeserialize, Discriminator};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use solana_sdk::{
+    compute_budget::{self, ComputeBudgetInstruction},
+    message::Message,
+    system_instruction::SystemInstruction,
+    system_program,
+};
+use t_vault::instruction;
+
+/// One instruction of a message, decoded for display.
+#[derive(Debug)]
+pub struct DecodedInstruction {
+    pub program_id: String,
+    pub program_name: String,
+    /// Instruction name, or `None` when the data did not match a known instruction.
+    pub name: Option<String>,
+    pub args: Vec<DecodedArg>,
+    pub accounts: Vec<DecodedAccount>,
+    /// Raw instruction data, base64 encoded.
+    pub data: String,
+}
+
+#[derive(Debug)]
+pub struct DecodedArg {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug)]
+pub struct DecodedAccount {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// A t-vault instruction, matched by its 8-byte Anchor discriminator.
+struct TVaultInstruction {
+    name: &'static str,
+    discriminator: [u8; 8],
+    decode_args: fn(&[u8]) -> Option<Vec<DecodedArg>>,
+}
+
+const T_VAULT_INSTRUCTIONS: &[TVaultInstruction] = &[TVaultInstruction {
+    name: "initialize",
+    discriminator: instruction::Initialize::DISCRIMINATOR,
+    decode_args: decode_initialize_args,
+}];
+
+fn decode_initialize_args(data: &[u8]) -> Option<Vec<DecodedArg>> {
+    instruction::Initialize::try_from_slice(data).ok()?;
+    Some(Vec::new())
+}
+
+/// Decodes every instruction in `message`, resolving accounts against its
+/// account table.
+pub fn decode_message(message: &Message) -> Vec<DecodedInstruction> {
+    message
+        .instructions
+        .iter()
+        .map(|ix| {
+            let program_id = message
+                .account_keys
+                .get(ix.program_id_index as usize)
+                .copied()
+                .unwrap_or_default();
+            let accounts = ix
+                .accounts
+                .iter()
+                .map(|index| {
+                    let index = *index as usize;
+                    DecodedAccount {
+                        pubkey: message
+                            .account_keys
+                            .get(index)
+                            .map(|key| key.to_string())
+                            .unwrap_or_else(|| format!("<missing account {}>", index)),
+                        is_signer: message.is_signer(index),
+                        is_writable: message.is_writable(index),
+                    }
+                })
+                .collect();
+
+            let (program_name, decoded) = if program_id == t_vault::id() {
+                ("T-Vault", decode_t_vault(&ix.data))
+            } else if program_id == compute_budget::id() {
+                ("Compute Budget", decode_compute_budget(&ix.data))
+            } else if program_id == system_program::id() {
+                ("System Program", decode_system(&ix.data))
+            } else {
+                ("Unknown program", None)
+            };
+            let (name, args) = match decoded {
+                Some((name, args)) => (Some(name), args),
+                None => (None, Vec::new()),
+            };
+
+            DecodedInstruction {
+                program_id: program_id.to_string(),
+                program_name: program_name.to_string(),
+                name,
+                args,
+                accounts,
+                data: BASE64.encode(&ix.data),
+            }
+        })
+        .collect()
+}
+
+fn decode_t_vault(data: &[u8]) -> Option<(String, Vec<DecodedArg>)> {
+    if data.len() < 8 {
+        return None;
+    }
+    let (discriminator, args) = data.split_at(8);
+    let ix = T_VAULT_INSTRUCTIONS
+        .iter()
+        .find(|ix| ix.discriminator == discriminator)?;
+    Some((ix.name.to_string(), (ix.decode_args)(args)?))
+}
+
+fn decode_compute_budget(data: &[u8]) -> Option<(String, Vec<DecodedArg>)> {
+    let decoded = match borsh::from_slice::<ComputeBudgetInstruction>(data).ok()? {
+        ComputeBudgetInstruction::RequestHeapFrame(bytes) => {
+            ("RequestHeapFrame", vec![arg("bytes", bytes)])
+        }
+        ComputeBudgetInstruction::SetComputeUnitLimit(units) => {
+            ("SetComputeUnitLimit", vec![arg("units", units)])
+        }
+        ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports) => (
+            "SetComputeUnitPrice",
+            vec![arg("micro_lamports", micro_lamports)],
+        ),
+        ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(bytes) => {
+            ("SetLoadedAccountsDataSizeLimit", vec![arg("bytes", bytes)])
+        }
+        ComputeBudgetInstruction::Unused => ("Unused", Vec::new()),
+    };
+    Some((decoded.0.to_string(), decoded.1))
+}
+
+fn decode_system(data: &[u8]) -> Option<(String, Vec<DecodedArg>)> {
+    let decoded = match bincode::deserialize::<SystemInstruction>(data).ok()? {
+        SystemInstruction::Transfer { lamports } => ("Transfer", vec![arg("lamports", lamports)]),
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => (
+            "CreateAccount",
+            vec![
+                arg("lamports", lamports),
+                arg("space", space),
+                arg("owner", owner),
+            ],
+        ),
+        SystemInstruction::Allocate { space } => ("Allocate", vec![arg("space", space)]),
+        SystemInstruction::Assign { owner } => ("Assign", vec![arg("owner", owner)]),
+        other => {
+            let debug = format!("{:?}", other);
+            let name = debug.split([' ', '(', '{']).next().unwrap_or_default();
+            return Some((name.to_string(), vec![arg("args", &debug)]));
+        }
+    };
+    Some((decoded.0.to_string(), decoded.1))
+}
+
+fn arg(name: &str, value: impl ToString) -> DecodedArg {
+    DecodedArg {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
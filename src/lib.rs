@@ -1,5 +1,21 @@
+pub mod app;
+pub mod config;
 pub mod repository;
+pub mod blockhash_cache;
+pub mod repository_error;
 pub mod schema;
-pub mod models;
+pub mod session;
+pub mod instruction_decoder;
 pub mod solana_transactions_repository;
-
+pub mod transaction_builder;
+pub mod transaction_preview;
+pub mod transaction_validation;
+pub mod priority_fee;
+pub mod program_errors;
+pub mod rate_limit;
+pub mod rpc_pool;
+pub mod signature_subscriber;
+pub mod solana_rpc;
+pub mod status_events;
+pub mod tracker;
+pub mod transaction_status;
@@ -1,66 +1,63 @@
 use std::{
-    str::FromStr,
+    net::SocketAddr,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use anchor_client::anchor_lang::InstructionData;
-use askama::Template;
-use askama_axum::{IntoResponse, Response};
-use axum::{
-    extract::Query,
-    http::StatusCode,
-    routing::{get, post},
-    Extension, Form,Router,
-};
-use base64::engine::general_purpose::STANDARD as BASE64;
-use base64::engine::Engine as _;
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::Utc;
 use deadpool_diesel::mysql::{Manager, Pool};
 use dotenv::dotenv;
-use serde::{Deserialize, Serialize};
-use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
-use solana_sdk::{
-    commitment_config::{CommitmentConfig, CommitmentLevel},
-    instruction::Instruction,
-    message::Message,
-    pubkey::Pubkey,
-    signature::Signature,
-    transaction::Transaction,
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+
+use t_vault_web_server::app::{app, AppState};
+use t_vault_web_server::blockhash_cache::BlockhashCache;
+use t_vault_web_server::config::Config;
+use t_vault_web_server::session::SessionConfig;
+use t_vault_web_server::signature_subscriber::{SignatureSubscriber, TrackingMode};
+use t_vault_web_server::rpc_pool::{RpcEndpoint, RpcPool};
+use t_vault_web_server::solana_rpc::SolanaRpc;
+use t_vault_web_server::solana_transactions_repository::SolanaTransaction;
+use t_vault_web_server::status_events;
+use t_vault_web_server::tracker::{
+    expire_unsigned, resolve_references, track_cycle, MAX_TRACKED_TRANSACTIONS,
 };
-
-use solana_transaction_status::TransactionConfirmationStatus;
-use t_vault::instruction;
-use t_vault_web_server::solana_transactions_repository::{NewSolanaTransaction, SolanaTransaction};
+use t_vault_web_server::transaction_validation::decode_transaction;
 use tokio::time::sleep;
 
-pub mod schema;
+/// How often the tracker sweeps pending transactions while the websocket is healthy.
+const WEBSOCKET_SWEEP_INTERVAL: Duration = Duration::from_secs(5);
 
-struct Config {
-    rpc_url: String,
-    database_url: String,
-}
-
-impl Config {
-    pub fn new() -> Self {
-        Config {
-            rpc_url: std::env::var("SOLANA_RPC_URL").expect("SOLANA_RPC_URL not set in env."),
-            database_url: std::env::var("DATABASE_URL").expect("DATABASE_URL not set in env."),
-        }
-    }
-}
+/// How often Solana Pay references are looked up, at an RPC call or more each.
+const REFERENCE_LOOKUP_INTERVAL: Duration = Duration::from_secs(2);
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
-    let config = Config::new();
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(
-        config.rpc_url,
-        CommitmentConfig {
-            commitment: CommitmentLevel::Processed,
-        },
-    ));
+    let config = Config::from_env();
+    // The timeout applies to every request, so a slow node fails over to the
+    // next endpoint instead of holding up the handler or tracker awaiting it.
+    let rpc_endpoints = config
+        .rpc_endpoints
+        .iter()
+        .map(|endpoint| {
+            RpcEndpoint::connect(
+                endpoint.clone(),
+                config.rpc_timeout,
+                CommitmentConfig {
+                    commitment: CommitmentLevel::Processed,
+                },
+            )
+        })
+        .collect();
+    let rpc_pool = Arc::new(RpcPool::new(rpc_endpoints, config.rpc_send_fanout));
+    let rpc_client: Arc<dyn SolanaRpc> = rpc_pool.clone();
+    let blockhash_cache = BlockhashCache::spawn(
+        rpc_client.clone(),
+        config.blockhash_refresh_interval,
+        config.blockhash_max_age,
+    );
     let manager = Manager::new(
         config.database_url.to_string(),
         deadpool_diesel::Runtime::Tokio1,
@@ -68,331 +65,162 @@ async fn main() {
     let pool = Pool::builder(manager).build().unwrap();
 
     let database_pool = Arc::new(pool);
+    let session_config = SessionConfig::new(config.session_secret.as_deref(), &config.public_url);
+    let config = Arc::new(config);
+
+    let status_events = status_events::channel();
+
+    let signature_subscriber = match config.tracking_mode {
+        TrackingMode::Polling => SignatureSubscriber::disabled(),
+        TrackingMode::Websocket | TrackingMode::Hybrid => SignatureSubscriber::spawn(
+            config.ws_url.clone(),
+            database_pool.clone(),
+            rpc_client.clone(),
+            status_events.clone(),
+        ),
+    };
 
     {
         let database_pool = database_pool.clone();
         let rpc_client = rpc_client.clone();
+        let signature_subscriber = signature_subscriber.clone();
+        let status_events = status_events.clone();
+        let tracking_mode = config.tracking_mode;
         tokio::spawn(async move {
+            let mut last_poll = Instant::now();
             loop {
-                let txs = SolanaTransaction::get_all_not_finalized_or_failed(&database_pool).await;
-
-                if let Ok(txs) = txs {
-                    if txs.len() > 0 {
-                        let epoch_data = rpc_client.get_epoch_info();
-                        if let Ok(epoch_data) = epoch_data {
-                            let latest_block_height = epoch_data.block_height;
-                            for tx in txs.iter() {
-                                if latest_block_height < tx.last_valid_block_height {
-                                    // block height ok
-                                    let sig =
-                                        Signature::from_str(&tx.tx_signature.as_ref().unwrap())
-                                            .unwrap();
-
-                                    let transaction_status =
-                                        rpc_client.get_signature_statuses(&[sig]);
-
-                                    if let Ok(tx_status_response) = &transaction_status {
-                                        let statuses = &tx_status_response.value;
+                sleep(Duration::from_millis(200)).await;
 
-                                        if let Some(status) = statuses[0].clone() {
-                                            println!("{:?}", status);
-                                            if let Some(confirmation) =
-                                                status.confirmation_status.clone()
-                                            {
-                                                match confirmation {
-                                                    TransactionConfirmationStatus::Processed => {
-                                                        if tx.status < 2 {
-                                                            // set status processing
-                                                        }
-                                                    }
-                                                    TransactionConfirmationStatus::Confirmed => {
-                                                        if tx.status < 3 {
-                                                            let _ = SolanaTransaction::set_status_confirmed(&database_pool, tx.id).await;
-                                                        }
-                                                    }
-                                                    TransactionConfirmationStatus::Finalized => {
-                                                        if tx.status < 4 {
-                                                            let _ = SolanaTransaction::set_status_finalized(&database_pool, tx.id).await;
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                } else {
-                                    // transaction failed
-                                    println!("Failed tx");
-                                    let _ =
-                                        SolanaTransaction::set_status_failed(&database_pool, tx.id)
-                                            .await;
-                                }
-                            }
-                        }
-                    }
+                // In websocket mode polling only sweeps for expired and missed
+                // transactions, unless the socket is down.
+                if tracking_mode == TrackingMode::Websocket
+                    && signature_subscriber.is_healthy()
+                    && last_poll.elapsed() < WEBSOCKET_SWEEP_INTERVAL
+                {
+                    continue;
+                }
+                last_poll = Instant::now();
+
+                match track_cycle(&database_pool, rpc_client.as_ref(), &status_events).await {
+                    Ok(stats) if stats.changed > 0 => println!(
+                        "Tracked {} txs with {} rpc calls and {} db round trips, {} changed",
+                        stats.tracked, stats.rpc_calls, stats.db_round_trips, stats.changed
+                    ),
+                    Ok(_) => {}
+                    Err(err) => println!("Tracker cycle failed: {:?}", err),
                 }
-                sleep(Duration::from_millis(200)).await;
             }
         });
     }
 
-    let app = Router::new()
-        // No Auth
-        .route("/styles.css", get(styles))
-        .route("/script.js", get(script))
-        .route("/", get(index))
-        .route("/tx-modal", get(handle_get_tx_modal))
-        .route("/tx-status", get(handle_get_tx_status))
-        .route("/tx-submit", post(handle_submit_tx))
-        .route("/tx-status-data", get(handle_get_tx_status_data))
-        .layer(Extension(database_pool))
-        .layer(Extension(rpc_client));
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    println!("Listener bound to port 3000");
-    println!("Serving listener..");
-    axum::serve(listener, app).await.unwrap();
-}
-
-#[derive(Template)]
-#[template(path = "index.html")]
-struct IndexTemplate;
-
-async fn index() -> impl IntoResponse {
-    return IndexTemplate;
-}
-
-async fn styles() -> impl IntoResponse {
-    Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "text/css")
-        .body(include_str!("../templates/styles.css").to_owned())
-        .unwrap()
-}
-
-async fn script() -> impl IntoResponse {
-    Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "text/javascript")
-        .body(include_str!("../templates/script.js").to_owned())
-        .unwrap()
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct Base64EncodedTransaction {
-    encoded_tx: String,
-}
-
-#[derive(Template)]
-#[template(path = "tx-modal.html")]
-struct TxModalTemplate {
-    tx_id: i32,
-    transaction_name: String,
-    button_id: String,
-    encoded_tx: String,
-}
-
-#[derive(Deserialize)]
-struct TxModalQueryParams {
-    tx_type: String,
-    pubkey: String,
-}
-
-// Building the tx
-async fn handle_get_tx_modal(
-    Query(query_params): Query<TxModalQueryParams>,
-    Extension(database_pool): Extension<Arc<Pool>>,
-    Extension(rpc_client): Extension<Arc<RpcClient>>,
-) -> impl IntoResponse {
-    let tx_type = query_params.tx_type;
-    let pubkey = Pubkey::from_str(&query_params.pubkey);
-
-    if let Ok(pubkey) = pubkey {
-        if tx_type == "initialize" {
-            let system_program_id = Pubkey::from_str(&t_vault::id().to_string()).unwrap();
-
-            let ix_data = instruction::Initialize {};
-
-            let ix = Instruction::new_with_bytes(system_program_id, &ix_data.data(), Vec::new());
-
-            println!("Created ix...");
-
-            //let signers = &[&keypair];
-
-            let (blockhash, last_valid_block_height) = rpc_client
-                .get_latest_blockhash_with_commitment(CommitmentConfig {
-                    commitment: CommitmentLevel::Confirmed,
-                })
-                .unwrap();
-            println!("Got latest blockhash");
-
-            let message = Message::new_with_blockhash(&[ix], Some(&pubkey), &blockhash);
-
-            let tx = Transaction::new_unsigned(message);
-            let serialized_tx = bincode::serialize(&tx).unwrap();
-
-            let encoded_tx = BASE64.encode(serialized_tx);
-
-            let now_utc: DateTime<Utc> = Utc::now();
-
-            let now_naive_with_ms = NaiveDateTime::from_timestamp_opt(
-                now_utc.timestamp(),
-                now_utc.timestamp_subsec_millis() as u32 * 1_000_000,
-            )
-            .expect("To get valid NaiveDateTime");
-            let new_db_tx = NewSolanaTransaction {
-                blockhash: blockhash.to_string(),
-                last_valid_block_height,
-                status: 0,
-                tx: encoded_tx.clone(),
-                created_at: now_naive_with_ms,
-                sent_at: None,
-            };
-
-            let db_result = SolanaTransaction::insert(&database_pool, new_db_tx).await;
-            if let Ok(tx_id) = db_result {
-                return (
-                    StatusCode::OK,
-                    TxModalTemplate {
-                        tx_id,
-                        transaction_name: "Initialize".to_string(),
-                        button_id: "initialize-button".to_string(),
-                        encoded_tx,
-                    }
-                    .to_string(),
-                );
+    {
+        // The wallet broadcasts Solana Pay transactions itself, so they are
+        // found on chain by their reference account.
+        let database_pool = database_pool.clone();
+        let rpc_client = rpc_client.clone();
+        let status_events = status_events.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(REFERENCE_LOOKUP_INTERVAL).await;
+
+                match resolve_references(&database_pool, rpc_client.as_ref(), &status_events).await
+                {
+                    Ok(stats) if stats.changed > 0 => println!(
+                        "Found {} Solana Pay txs with {} rpc calls",
+                        stats.changed, stats.rpc_calls
+                    ),
+                    Ok(_) => {}
+                    Err(err) => println!("Reference lookup failed: {:?}", err),
+                }
             }
-        }
-
-        return (StatusCode::BAD_REQUEST, "Invalid tx_type".to_string());
-    } else {
-        (StatusCode::BAD_REQUEST, "Invalid pubkey".to_string())
+        });
     }
-}
-
-#[derive(Template)]
-#[template(path = "tx-status.html")]
-struct TxStatusTemplate {
-    tx_signature: String,
-}
-
-#[derive(Deserialize)]
-struct TxStatusQueryParams {
-    tx_signature: String,
-}
-
-async fn handle_get_tx_status(
-    Query(query_params): Query<TxStatusQueryParams>,
-) -> impl IntoResponse {
-    let tx_signature = query_params.tx_signature;
-
-    (
-        StatusCode::OK,
-        TxStatusTemplate { tx_signature }.to_string(),
-    )
-}
-
-async fn handle_get_tx_status_data(
-    Extension(rpc_client): Extension<Arc<RpcClient>>,
-    Query(query_params): Query<TxStatusQueryParams>,
-) -> impl IntoResponse {
-    let tx_signature = query_params.tx_signature;
-
-    let sig = Signature::from_str(&tx_signature).unwrap();
 
-    let transaction_status = rpc_client.get_signature_statuses(&[sig]);
+    {
+        // Resend signed transactions until they confirm or their blockhash expires,
+        // since RPC nodes drop them under congestion.
+        let database_pool = database_pool.clone();
+        let rpc_client = rpc_client.clone();
+        let rebroadcast_interval = config.rebroadcast_interval;
+        tokio::spawn(async move {
+            loop {
+                sleep(rebroadcast_interval).await;
+
+                let latest_block_height = match rpc_client.get_epoch_info().await {
+                    Ok(epoch_data) => epoch_data.block_height,
+                    Err(_) => continue,
+                };
+
+                // Unsigned rows never reach the tracker, so they expire here.
+                match expire_unsigned(&database_pool, latest_block_height).await {
+                    Ok(0) => {}
+                    Ok(expired) => println!("Expired {} unsigned txs", expired),
+                    Err(err) => println!("Failed to expire unsigned txs: {:?}", err),
+                }
 
-    if let Ok(tx_status_response) = &transaction_status {
-        let statuses = &tx_status_response.value;
+                let txs = SolanaTransaction::get_all_pending_rebroadcast(
+                    &database_pool,
+                    latest_block_height,
+                    MAX_TRACKED_TRANSACTIONS,
+                )
+                .await;
+                let txs = match txs {
+                    Ok(txs) if !txs.is_empty() => txs,
+                    _ => continue,
+                };
+
+                let now = Utc::now().naive_utc();
+                for tx in txs.iter() {
+                    let last_sent_at = tx.last_resent_at.or(tx.sent_at);
+                    if let Some(last_sent_at) = last_sent_at {
+                        if (now - last_sent_at).to_std().unwrap_or_default() < rebroadcast_interval
+                        {
+                            continue;
+                        }
+                    }
 
-        if let Some(status) = statuses[0].clone() {
-            println!("{:?}", status);
-            if let Some(confirmation) = status.confirmation_status.clone() {
-                return (StatusCode::OK, format!("{:?}", confirmation));
+                    if let Some(signed_tx) = decode_transaction(&tx.tx) {
+                        let send_config = RpcSendTransactionConfig {
+                            skip_preflight: true,
+                            preflight_commitment: None,
+                            encoding: None,
+                            max_retries: Some(0),
+                            min_context_slot: None,
+                        };
+                        if rpc_client
+                            .send_transaction_with_config(&signed_tx, send_config)
+                            .await
+                            .is_ok()
+                        {
+                            let _ =
+                                SolanaTransaction::record_rebroadcast(&database_pool, tx.id, now)
+                                    .await;
+                        }
+                    }
+                }
             }
-            return (StatusCode::OK, format!("{:?}", status));
-        }
-    }
-
-    (StatusCode::OK, "Signature status not found".to_string())
-}
-
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct SubmitTxPayload {
-    tx_id: i32,
-    encoded_serialized_tx: String,
-}
-
-async fn handle_submit_tx(
-    Extension(database_pool): Extension<Arc<Pool>>,
-    Extension(rpc_client): Extension<Arc<RpcClient>>,
-    Form(tx_data): Form<SubmitTxPayload>,
-) -> impl IntoResponse {
-    let db_tx = SolanaTransaction::get_by_id(&database_pool, tx_data.tx_id).await;
-
-    if db_tx.is_err() {
-        return (
-            StatusCode::BAD_REQUEST,
-            "Invalid associated tx_id".to_string(),
-        );
+        });
     }
-    // TODO: more transaction validations at some point
-
-    let serialized_tx = BASE64
-        .decode(tx_data.encoded_serialized_tx.clone())
-        .unwrap();
-    let tx: Transaction = bincode::deserialize(&serialized_tx).unwrap();
-
-    let send_config = RpcSendTransactionConfig {
-        skip_preflight: false,
-        preflight_commitment: Some(CommitmentLevel::Processed),
-        encoding: None,
-        max_retries: None,
-        min_context_slot: None,
-    };
 
-    let signature_result = rpc_client.send_transaction_with_config(&tx, send_config);
+    let app = app(AppState {
+        database_pool,
+        rpc_client,
+        rpc_pool,
+        blockhash_cache,
+        session_config,
+        signature_subscriber,
+        status_events,
+        config,
+    });
 
-    let now_utc: DateTime<Utc> = Utc::now();
-
-    let sent_at = NaiveDateTime::from_timestamp_opt(
-        now_utc.timestamp(),
-        now_utc.timestamp_subsec_millis() as u32 * 1_000_000,
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    println!("Listener bound to port 3000");
+    println!("Serving listener..");
+    // Peer addresses feed the per-IP limit on unauthenticated endpoints.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .await
     .unwrap();
-
-    if let Ok(signature) = signature_result {
-        let db_result = SolanaTransaction::set_status_sent(
-            &database_pool,
-            tx_data.tx_id,
-            signature.to_string(),
-            sent_at,
-            tx_data.encoded_serialized_tx,
-        )
-        .await;
-        if let Ok(_) = db_result {
-            println!("Successfully updated transaction in db!");
-        }
-        return (
-            StatusCode::OK,
-            TxStatusTemplate {
-                tx_signature: signature.to_string(),
-            }
-            .to_string(),
-        );
-    }
-
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        "Failed to submit tx".to_string(),
-    )
 }
-
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
-struct User {
-    id: i32,
-    name: String,
-    age: i32,
-}
-
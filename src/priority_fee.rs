@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction, hash::Hash,
+    instruction::Instruction, message::Message, pubkey::Pubkey, transaction::Transaction,
+};
+
+use crate::solana_rpc::SolanaRpc;
+
+/// Largest compute unit limit a transaction may request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Headroom added on top of the simulated compute units, in percent.
+const COMPUTE_UNIT_MARGIN_PERCENT: u64 = 10;
+
+/// How the compute unit price is chosen for built transactions.
+///
+/// Parsed from `off`, `fixed:<micro_lamports>` or `percentile:<0-100>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriorityFeeMode {
+    Off,
+    Fixed(u64),
+    Percentile(u8),
+}
+
+impl FromStr for PriorityFeeMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "off" => Ok(PriorityFeeMode::Off),
+            Some(("fixed", micro_lamports)) => micro_lamports
+                .parse()
+                .map(PriorityFeeMode::Fixed)
+                .map_err(|_| ()),
+            Some(("percentile", percentile)) => match percentile.parse() {
+                Ok(percentile) if percentile <= 100 => Ok(PriorityFeeMode::Percentile(percentile)),
+                _ => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+/// Compute budget settings prepended to a transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub compute_unit_limit: Option<u32>,
+    pub compute_unit_price: Option<u64>,
+}
+
+impl ComputeBudget {
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let mut ixs = Vec::new();
+        if let Some(compute_unit_limit) = self.compute_unit_limit {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(
+                compute_unit_limit,
+            ));
+        }
+        if let Some(compute_unit_price) = self.compute_unit_price {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+                compute_unit_price,
+            ));
+        }
+        ixs
+    }
+
+    /// Returns `ixs` with the compute budget instructions in front.
+    pub fn prepend_to(&self, ixs: &[Instruction]) -> Vec<Instruction> {
+        let mut with_budget = self.instructions();
+        with_budget.extend_from_slice(ixs);
+        with_budget
+    }
+}
+
+/// Picks a compute budget for `ixs`, or none at all when the mode is `Off`.
+pub async fn estimate_compute_budget(
+    rpc_client: &dyn SolanaRpc,
+    mode: PriorityFeeMode,
+    ixs: &[Instruction],
+    payer: &Pubkey,
+    blockhash: &Hash,
+) -> ComputeBudget {
+    if mode == PriorityFeeMode::Off {
+        return ComputeBudget::default();
+    }
+
+    ComputeBudget {
+        compute_unit_limit: estimate_compute_unit_limit(rpc_client, ixs, payer, blockhash).await,
+        compute_unit_price: estimate_priority_fee(rpc_client, mode, &writable_accounts(ixs, payer))
+            .await,
+    }
+}
+
+/// Micro-lamports per compute unit for a transaction writing to `writable_accounts`.
+pub async fn estimate_priority_fee(
+    rpc_client: &dyn SolanaRpc,
+    mode: PriorityFeeMode,
+    writable_accounts: &[Pubkey],
+) -> Option<u64> {
+    match mode {
+        PriorityFeeMode::Off => None,
+        PriorityFeeMode::Fixed(micro_lamports) => Some(micro_lamports),
+        PriorityFeeMode::Percentile(percentile) => {
+            let mut fees: Vec<u64> = rpc_client
+                .get_recent_prioritization_fees(writable_accounts)
+                .await
+                .ok()?
+                .iter()
+                .map(|fee| fee.prioritization_fee)
+                .collect();
+            if fees.is_empty() {
+                return Some(0);
+            }
+            fees.sort_unstable();
+
+            let index = (fees.len() - 1) * percentile as usize / 100;
+            Some(fees[index])
+        }
+    }
+}
+
+/// Simulates `ixs` at the maximum limit and returns the units consumed plus a margin.
+pub async fn estimate_compute_unit_limit(
+    rpc_client: &dyn SolanaRpc,
+    ixs: &[Instruction],
+    payer: &Pubkey,
+    blockhash: &Hash,
+) -> Option<u32> {
+    let budget = ComputeBudget {
+        compute_unit_limit: Some(MAX_COMPUTE_UNIT_LIMIT),
+        compute_unit_price: None,
+    };
+    let message = Message::new_with_blockhash(&budget.prepend_to(ixs), Some(payer), blockhash);
+    let tx = Transaction::new_unsigned(message);
+
+    let simulate_config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::processed()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let simulation = rpc_client
+        .simulate_transaction_with_config(&tx, simulate_config)
+        .await
+        .ok()?
+        .value;
+    if simulation.err.is_some() {
+        return None;
+    }
+
+    let units_consumed = simulation.units_consumed?;
+    let with_margin = units_consumed + units_consumed * COMPUTE_UNIT_MARGIN_PERCENT / 100;
+    Some(with_margin.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32)
+}
+
+/// The fee payer plus every account an instruction marks writable.
+fn writable_accounts(ixs: &[Instruction], payer: &Pubkey) -> Vec<Pubkey> {
+    let mut accounts = vec![*payer];
+    for account in ixs.iter().flat_map(|ix| ix.accounts.iter()) {
+        if account.is_writable && !accounts.contains(&account.pubkey) {
+            accounts.push(account.pubkey);
+        }
+    }
+    accounts
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+
+use anchor_client::anchor_lang::error::{ErrorCode, ERROR_CODE_OFFSET};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_config::RpcTransactionConfig,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::UiTransactionEncoding;
+use t_vault::error::ErrorCode as TVaultErrorCode;
+
+use crate::solana_rpc::SolanaRpc;
+
+/// A program error code resolved to its Anchor name and message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedProgramError {
+    pub code: u32,
+    pub name: String,
+    pub message: String,
+}
+
+impl fmt::Display for DecodedProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.name, self.code, self.message)
+    }
+}
+
+/// Anchor framework errors, which every Anchor program can return below
+/// `ERROR_CODE_OFFSET`.
+const ANCHOR_ERRORS: &[ErrorCode] = &[
+    ErrorCode::InstructionMissing,
+    ErrorCode::InstructionFallbackNotFound,
+    ErrorCode::InstructionDidNotDeserialize,
+    ErrorCode::InstructionDidNotSerialize,
+    ErrorCode::IdlInstructionStub,
+    ErrorCode::IdlInstructionInvalidProgram,
+    ErrorCode::IdlAccountNotEmpty,
+    ErrorCode::EventInstructionStub,
+    ErrorCode::ConstraintMut,
+    ErrorCode::ConstraintHasOne,
+    ErrorCode::ConstraintSigner,
+    ErrorCode::ConstraintRaw,
+    ErrorCode::ConstraintOwner,
+    ErrorCode::ConstraintRentExempt,
+    ErrorCode::ConstraintSeeds,
+    ErrorCode::ConstraintExecutable,
+    ErrorCode::ConstraintState,
+    ErrorCode::ConstraintAssociated,
+    ErrorCode::ConstraintAssociatedInit,
+    ErrorCode::ConstraintClose,
+    ErrorCode::ConstraintAddress,
+    ErrorCode::ConstraintZero,
+    ErrorCode::ConstraintTokenMint,
+    ErrorCode::ConstraintTokenOwner,
+    ErrorCode::ConstraintMintMintAuthority,
+    ErrorCode::ConstraintMintFreezeAuthority,
+    ErrorCode::ConstraintMintDecimals,
+    ErrorCode::ConstraintSpace,
+    ErrorCode::ConstraintAccountIsNone,
+    ErrorCode::ConstraintTokenTokenProgram,
+    ErrorCode::ConstraintMintTokenProgram,
+    ErrorCode::ConstraintAssociatedTokenTokenProgram,
+    ErrorCode::RequireViolated,
+    ErrorCode::RequireEqViolated,
+    ErrorCode::RequireKeysEqViolated,
+    ErrorCode::RequireNeqViolated,
+    ErrorCode::RequireKeysNeqViolated,
+    ErrorCode::RequireGtViolated,
+    ErrorCode::RequireGteViolated,
+    ErrorCode::AccountDiscriminatorAlreadySet,
+    ErrorCode::AccountDiscriminatorNotFound,
+    ErrorCode::AccountDiscriminatorMismatch,
+    ErrorCode::AccountDidNotDeserialize,
+    ErrorCode::AccountDidNotSerialize,
+    ErrorCode::AccountNotEnoughKeys,
+    ErrorCode::AccountNotMutable,
+    ErrorCode::AccountOwnedByWrongProgram,
+    ErrorCode::InvalidProgramId,
+    ErrorCode::InvalidProgramExecutable,
+    ErrorCode::AccountNotSigner,
+    ErrorCode::AccountNotSystemOwned,
+    ErrorCode::AccountNotInitialized,
+    ErrorCode::AccountNotProgramData,
+    ErrorCode::AccountNotAssociatedTokenAccount,
+    ErrorCode::AccountSysvarMismatch,
+    ErrorCode::AccountReallocExceedsLimit,
+    ErrorCode::AccountDuplicateReallocs,
+    ErrorCode::DeclaredProgramIdMismatch,
+    ErrorCode::TryingToInitPayerAsProgramAccount,
+    ErrorCode::Deprecated,
+];
+
+/// t-vault's own `#[error_code]` errors, numbered from `ERROR_CODE_OFFSET`.
+/// Keep in step with `t_vault::error::ErrorCode`.
+const T_VAULT_ERRORS: &[TVaultErrorCode] = &[TVaultErrorCode::AlreadyInitialized];
+
+/// Looks up an Anchor framework error code.
+pub fn decode_anchor_error(code: u32) -> Option<DecodedProgramError> {
+    ANCHOR_ERRORS
+        .iter()
+        .find(|error| u32::from(**error) == code)
+        .map(|error| DecodedProgramError {
+            code,
+            name: error.name(),
+            message: error.to_string(),
+        })
+}
+
+/// Looks up a t-vault error code.
+pub fn decode_t_vault_error(code: u32) -> Option<DecodedProgramError> {
+    T_VAULT_ERRORS
+        .iter()
+        .find(|error| u32::from(**error) == code)
+        .map(|error| DecodedProgramError {
+            code,
+            name: error.name(),
+            message: error.to_string(),
+        })
+}
+
+/// Finds the error `code` in the `AnchorError ... Error Code: <name>. Error
+/// Number: <code>. Error Message: <message>.` line Anchor logs before failing.
+/// Fallback for t-vault errors missing from `T_VAULT_ERRORS`, e.g. from a
+/// newer program deployment.
+pub fn decode_anchor_error_log(code: u32, logs: &[String]) -> Option<DecodedProgramError> {
+    logs.iter().find_map(|log| {
+        let log = log.strip_prefix("Program log: ").unwrap_or(log);
+        if !log.starts_with("AnchorError") {
+            return None;
+        }
+        let (_, rest) = log.split_once("Error Code: ")?;
+        let (name, rest) = rest.split_once(". Error Number: ")?;
+        let (number, message) = rest.split_once(". Error Message: ")?;
+        if number.parse::<u32>().ok()? != code {
+            return None;
+        }
+        Some(DecodedProgramError {
+            code,
+            name: name.to_string(),
+            message: message.trim_end_matches('.').to_string(),
+        })
+    })
+}
+
+/// Decodes a custom error raised by a t-vault instruction. Errors from other
+/// programs, such as the compute budget program, are left alone.
+pub fn decode_transaction_error(
+    error: &TransactionError,
+    tx: Option<&Transaction>,
+    logs: &[String],
+) -> Option<DecodedProgramError> {
+    let TransactionError::InstructionError(index, InstructionError::Custom(code)) = error else {
+        return None;
+    };
+
+    let program_id = tx.and_then(|tx| instruction_program_id(tx, *index as usize));
+    if program_id.is_some() && program_id != Some(t_vault::id()) {
+        return None;
+    }
+
+    if *code < ERROR_CODE_OFFSET {
+        decode_anchor_error(*code)
+    } else {
+        decode_t_vault_error(*code).or_else(|| decode_anchor_error_log(*code, logs))
+    }
+}
+
+/// Human-readable message for a transaction error, decoded when possible.
+pub fn describe_transaction_error(
+    error: &TransactionError,
+    tx: Option<&Transaction>,
+    logs: &[String],
+) -> String {
+    match decode_transaction_error(error, tx, logs) {
+        Some(decoded) => decoded.to_string(),
+        None => error.to_string(),
+    }
+}
+
+/// Decoded simulation error when the RPC node rejected a transaction in preflight.
+pub fn describe_preflight_failure(err: &ClientError, tx: &Transaction) -> Option<String> {
+    let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+        data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+        ..
+    }) = err.kind()
+    else {
+        return None;
+    };
+    let error = result.err.as_ref()?;
+    let logs = result.logs.as_deref().unwrap_or_default();
+    Some(describe_transaction_error(error, Some(tx), logs))
+}
+
+fn instruction_program_id(tx: &Transaction, index: usize) -> Option<Pubkey> {
+    let ix = tx.message.instructions.get(index)?;
+    tx.message
+        .account_keys
+        .get(ix.program_id_index as usize)
+        .copied()
+}
+
+/// Program logs of a landed transaction, used to decode its custom error.
+pub async fn transaction_logs(rpc_client: &dyn SolanaRpc, signature: &Signature) -> Vec<String> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    rpc_client
+        .get_transaction_with_config(signature, config)
+        .await
+        .ok()
+        .and_then(|tx| tx.transaction.meta)
+        .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom_error(code: u32) -> TransactionError {
+        TransactionError::InstructionError(0, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn decodes_anchor_and_t_vault_codes() {
+        let anchor =
+            decode_transaction_error(&custom_error(ErrorCode::ConstraintMut.into()), None, &[])
+                .unwrap();
+        assert_eq!(anchor.name, "ConstraintMut");
+
+        let t_vault = T_VAULT_ERRORS[0];
+        let decoded = decode_transaction_error(&custom_error(t_vault.into()), None, &[]).unwrap();
+        assert_eq!(decoded.code, u32::from(t_vault));
+        assert_eq!(decoded.name, t_vault.name());
+        assert_eq!(decoded.message, t_vault.to_string());
+    }
+
+    #[test]
+    fn falls_back_to_logs_for_unknown_codes() {
+        let code = ERROR_CODE_OFFSET + 999;
+        let logs = vec![format!(
+            "Program log: AnchorError occurred. Error Code: VaultLocked. Error Number: {}. Error Message: Vault is locked.",
+            code
+        )];
+        let decoded = decode_transaction_error(&custom_error(code), None, &logs).unwrap();
+        assert_eq!(decoded.name, "VaultLocked");
+        assert_eq!(decoded.message, "Vault is locked");
+        assert!(decode_transaction_error(&custom_error(code), None, &[]).is_none());
+    }
+}
@@ -1,99 +1,188 @@
+use crate::repository_error::{RepositoryError, RepositoryResult};
 use crate::schema::users;
+use crate::schema::users::dsl;
+use crate::solana_transactions_repository::last_insert_id;
 use diesel::mysql::MysqlConnection;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
+/// Most users `get_all` returns in one page.
+pub const MAX_USERS_PAGE_SIZE: i64 = 100;
+
+/// A wallet profile. Rows created before profiles were tied to wallets have
+/// no pubkey.
 #[derive(Debug, Serialize, Queryable, Selectable, QueryableByName)]
 #[diesel(table_name = users)]
 pub struct UserDb {
     pub id: i32,
     pub name: String,
     pub age: Option<i32>,
+    pub pubkey: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Insertable)]
+#[diesel(table_name = users)]
 pub struct NewUserDb {
     pub name: String,
     pub age: Option<i32>,
+    pub pubkey: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct UsersFilter {
+    /// Matches names containing this text.
     pub name: Option<String>,
     pub age: Option<i32>,
+    /// Returns users with an id above this one.
+    pub cursor: Option<i32>,
+    pub limit: Option<i64>,
 }
 
 pub async fn insert(
     pool: &deadpool_diesel::mysql::Pool,
     new_user: NewUserDb,
-) -> Result<UserDb, ()> {
-    let conn = pool.get().await;
-    if let Ok(conn) = conn {
-        conn.interact(move |conn: &mut MysqlConnection| {
-            diesel::sql_query("INSERT INTO users (name, age) VALUES (?, ?)")
-                .bind::<diesel::sql_types::Text, _>(&new_user.name)
-                .bind::<diesel::sql_types::Nullable<diesel::sql_types::Integer>, _>(&new_user.age)
-                .execute(conn)
-                .expect("Error inserting user");
-
-            Ok(UserDb {
-                id: 0,
-                name: new_user.name.clone(),
-                age: new_user.age,
+) -> RepositoryResult<UserDb> {
+    let conn = pool.get().await?;
+    let user = conn
+        .interact(move |conn: &mut MysqlConnection| {
+            conn.transaction::<_, RepositoryError, _>(|conn| {
+                diesel::insert_into(dsl::users)
+                    .values(&new_user)
+                    .execute(conn)?;
+
+                // Read on the connection that ran the INSERT, see
+                // `SolanaTransaction::insert`.
+                let id = diesel::select(last_insert_id()).get_result::<u64>(conn)?;
+                Ok(UserDb {
+                    id: id as i32,
+                    name: new_user.name,
+                    age: new_user.age,
+                    pubkey: new_user.pubkey,
+                })
             })
         })
-        .await
-        .map_err(|_| ())?
-    } else {
-        Err(())
+        .await??;
+
+    Ok(user)
+}
+
+pub async fn get(pool: &deadpool_diesel::mysql::Pool, id: i32) -> RepositoryResult<UserDb> {
+    let conn = pool.get().await?;
+    let user = conn
+        .interact(move |conn: &mut MysqlConnection| {
+            diesel::sql_query("SELECT id, name, age, pubkey FROM users WHERE id = ?")
+                .bind::<diesel::sql_types::Integer, _>(id)
+                .get_result::<UserDb>(conn)
+        })
+        .await??;
+
+    Ok(user)
+}
+
+pub async fn get_by_pubkey(
+    pool: &deadpool_diesel::mysql::Pool,
+    pubkey: String,
+) -> RepositoryResult<UserDb> {
+    let conn = pool.get().await?;
+    let user = conn
+        .interact(move |conn: &mut MysqlConnection| {
+            diesel::sql_query("SELECT id, name, age, pubkey FROM users WHERE pubkey = ?")
+                .bind::<diesel::sql_types::Text, _>(pubkey)
+                .get_result::<UserDb>(conn)
+        })
+        .await??;
+
+    Ok(user)
+}
+
+/// The profile for `pubkey`, created with `name` if the wallet has none yet.
+pub async fn get_or_create_by_pubkey(
+    pool: &deadpool_diesel::mysql::Pool,
+    pubkey: String,
+    name: String,
+) -> RepositoryResult<UserDb> {
+    match get_by_pubkey(pool, pubkey.clone()).await {
+        Err(RepositoryError::NotFound) => {}
+        result => return result,
+    }
+
+    let new_user = NewUserDb {
+        name,
+        age: None,
+        pubkey: Some(pubkey.clone()),
+    };
+    match insert(pool, new_user).await {
+        // Another sign-in for the same wallet created it first.
+        Err(RepositoryError::Conflict(_)) => get_by_pubkey(pool, pubkey).await,
+        result => result,
     }
 }
 
-pub async fn get(pool: &deadpool_diesel::mysql::Pool, id: i32) -> Result<UserDb, ()> {
-    let conn = pool.get().await;
-    if let Ok(conn) = conn {
-        let res = conn
-            .interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("SELECT id, name, age FROM users WHERE id = ?")
-                    .bind::<diesel::sql_types::Integer, _>(id)
-                    .get_result::<UserDb>(conn)
+/// Updates the name and age of the profile for `pubkey`.
+pub async fn update_profile(
+    pool: &deadpool_diesel::mysql::Pool,
+    pubkey: String,
+    name: String,
+    age: Option<i32>,
+) -> RepositoryResult<UserDb> {
+    let conn = pool.get().await?;
+    let user = conn
+        .interact(move |conn: &mut MysqlConnection| {
+            conn.transaction::<_, RepositoryError, _>(|conn| {
+                let updated = diesel::update(dsl::users.filter(dsl::pubkey.eq(&pubkey)))
+                    .set((dsl::name.eq(name), dsl::age.eq(age)))
+                    .execute(conn)?;
+                if updated == 0 {
+                    return Err(RepositoryError::NotFound);
+                }
+                Ok(dsl::users
+                    .filter(dsl::pubkey.eq(pubkey))
+                    .select(UserDb::as_select())
+                    .first::<UserDb>(conn)?)
             })
-            .await
-            .map_err(|_| ());
-
-        if let Ok(Ok(user)) = res {
-            return Ok(user);
-        } else {
-            return Err(());
-        }
-    } else {
-        return Err(());
-    }
+        })
+        .await??;
+
+    Ok(user)
 }
 
+/// Users matching `filter`, ordered by id, at most `MAX_USERS_PAGE_SIZE`.
 pub async fn get_all(
     pool: &deadpool_diesel::mysql::Pool,
-    _filter: UsersFilter,
-) -> Result<Vec<UserDb>, ()> {
-    let conn = pool.get().await;
-    if let Ok(conn) = conn {
-        let res = conn
-            .interact(move |conn: &mut MysqlConnection| {
-                let base_query = "SELECT id, name, age FROM users";
+    filter: UsersFilter,
+) -> RepositoryResult<Vec<UserDb>> {
+    let conn = pool.get().await?;
+    let users = conn
+        .interact(move |conn: &mut MysqlConnection| {
+            let mut query = dsl::users.into_boxed();
+            if let Some(name) = filter.name {
+                let pattern = format!(
+                    "%{}%",
+                    name.replace('\\', "\\\\")
+                        .replace('%', "\\%")
+                        .replace('_', "\\_")
+                );
+                query = query.filter(dsl::name.like(pattern));
+            }
+            if let Some(age) = filter.age {
+                query = query.filter(dsl::age.eq(age));
+            }
+            if let Some(cursor) = filter.cursor {
+                query = query.filter(dsl::id.gt(cursor));
+            }
 
-                let query = diesel::sql_query(base_query);
+            query
+                .order(dsl::id.asc())
+                .limit(
+                    filter
+                        .limit
+                        .unwrap_or(MAX_USERS_PAGE_SIZE)
+                        .clamp(1, MAX_USERS_PAGE_SIZE),
+                )
+                .select(UserDb::as_select())
+                .load::<UserDb>(conn)
+        })
+        .await??;
 
-                query.load::<UserDb>(conn)
-            })
-            .await
-            .map_err(|_| ());
-
-        if let Ok(Ok(users)) = res {
-            return Ok(users);
-        } else {
-            return Err(());
-        }
-    } else {
-        Err(())
-    }
+    Ok(users)
 }
@@ -0,0 +1,69 @@
+use std::fmt;
+
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+use crate::transaction_status::TransactionStatus;
+
+/// Why a repository call failed.
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// No connection could be taken from the pool.
+    Pool(String),
+    /// The blocking database closure panicked or was aborted.
+    Interact(String),
+    /// The requested row does not exist or did not match the update.
+    NotFound,
+    /// The write violates a constraint or conflicts with the row's current state.
+    Conflict(String),
+    /// The row's current status does not allow the requested one.
+    InvalidTransition {
+        from: TransactionStatus,
+        to: TransactionStatus,
+    },
+    /// Any other database error.
+    Database(DieselError),
+}
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::Pool(err) => write!(f, "Database pool unavailable: {}", err),
+            RepositoryError::Interact(err) => write!(f, "Database interaction failed: {}", err),
+            RepositoryError::NotFound => write!(f, "Record not found"),
+            RepositoryError::Conflict(err) => write!(f, "Conflict: {}", err),
+            RepositoryError::InvalidTransition { from, to } => {
+                write!(f, "Invalid status transition from {} to {}", from, to)
+            }
+            RepositoryError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<deadpool_diesel::mysql::PoolError> for RepositoryError {
+    fn from(err: deadpool_diesel::mysql::PoolError) -> Self {
+        RepositoryError::Pool(err.to_string())
+    }
+}
+
+impl From<deadpool_diesel::InteractError> for RepositoryError {
+    fn from(err: deadpool_diesel::InteractError) -> Self {
+        RepositoryError::Interact(err.to_string())
+    }
+}
+
+impl From<DieselError> for RepositoryError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => RepositoryError::NotFound,
+            DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation | DatabaseErrorKind::ForeignKeyViolation,
+                info,
+            ) => RepositoryError::Conflict(info.message().to_string()),
+            err => RepositoryError::Database(err),
+        }
+    }
+}
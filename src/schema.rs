@@ -20,6 +20,25 @@ diesel::table! {
         priority_fee -> Nullable<Unsigned<Integer>>,
         #[max_length = 200]
         tx_signature -> Nullable<Varchar>,
+        send_attempts -> Unsigned<Integer>,
+        last_resent_at -> Nullable<Datetime>,
+        #[max_length = 1000]
+        tx_error -> Nullable<Varchar>,
+        failed_instruction_index -> Nullable<Unsigned<Tinyint>>,
+        #[max_length = 1000]
+        tx_error_message -> Nullable<Varchar>,
+        #[max_length = 44]
+        owner -> Varchar,
+        #[max_length = 32]
+        tx_type -> Varchar,
+        #[max_length = 44]
+        program_id -> Varchar,
+        params -> Nullable<Json>,
+        #[max_length = 16]
+        source -> Varchar,
+        #[max_length = 44]
+        reference -> Nullable<Varchar>,
+        fee -> Nullable<Unsigned<Bigint>>,
     }
 }
 
@@ -29,6 +48,8 @@ diesel::table! {
         #[max_length = 255]
         name -> Varchar,
         age -> Nullable<Integer>,
+        #[max_length = 44]
+        pubkey -> Nullable<Varchar>,
     }
 }
 
@@ -0,0 +1,260 @@
+use std::fmt;
+use std::str::FromStr;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap, StatusCode},
+};
+use axum_extra::extract::cookie::{Cookie, Key, PrivateCookieJar, SameSite};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+const SESSION_COOKIE: &str = "session";
+const CHALLENGE_COOKIE: &str = "siws_challenge";
+
+/// How long a wallet has to sign the challenge.
+const CHALLENGE_TTL_MINUTES: i64 = 5;
+
+const SESSION_TTL_HOURS: i64 = 24;
+
+const NONCE_LENGTH: usize = 24;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignInError {
+    /// No challenge cookie, or it was issued for another wallet.
+    MissingChallenge,
+    ChallengeExpired,
+    InvalidSignature,
+}
+
+impl fmt::Display for SignInError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignInError::MissingChallenge => write!(f, "No sign-in challenge for this wallet"),
+            SignInError::ChallengeExpired => write!(f, "Sign-in challenge expired"),
+            SignInError::InvalidSignature => write!(f, "Invalid sign-in signature"),
+        }
+    }
+}
+
+impl std::error::Error for SignInError {}
+
+/// Issues and checks Sign-In With Solana challenges, and the session cookies
+/// handed out once a wallet has signed one.
+#[derive(Clone)]
+pub struct SessionConfig {
+    key: Key,
+    /// The site's public url, shown to the user in the message they sign.
+    public_url: String,
+    secure: bool,
+}
+
+impl SessionConfig {
+    /// `secret` must be at least 64 bytes. Without one a random key is used,
+    /// so sessions do not survive a restart.
+    pub fn new(secret: Option<&str>, public_url: &str) -> Self {
+        let key = match secret {
+            Some(secret) => {
+                Key::try_from(secret.as_bytes()).expect("SESSION_SECRET must be at least 64 bytes.")
+            }
+            None => {
+                println!("SESSION_SECRET not set, sessions will not survive a restart");
+                Key::generate()
+            }
+        };
+        SessionConfig {
+            key,
+            public_url: public_url.to_string(),
+            secure: public_url.starts_with("https://"),
+        }
+    }
+
+    /// Message the wallet is asked to sign to prove it owns `pubkey`.
+    fn sign_in_message(&self, pubkey: &Pubkey, nonce: &str, issued_at: DateTime<Utc>) -> String {
+        let domain = self
+            .public_url
+            .split("://")
+            .last()
+            .unwrap_or(&self.public_url);
+        format!(
+            "{} wants you to sign in with your Solana account:\n{}\n\nURI: {}\nVersion: 1\nNonce: {}\nIssued At: {}\nExpiration Time: {}",
+            domain,
+            pubkey,
+            self.public_url,
+            nonce,
+            issued_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            (issued_at + Duration::minutes(CHALLENGE_TTL_MINUTES))
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        )
+    }
+
+    fn cookie(&self, name: &'static str, value: String) -> Cookie<'static> {
+        Cookie::build((name, value))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(self.secure)
+            .build()
+    }
+
+    /// Starts a sign-in for `pubkey`. Returns the message to sign and a jar
+    /// holding the encrypted challenge.
+    pub fn issue_challenge(&self, pubkey: &Pubkey) -> (PrivateCookieJar, String) {
+        let nonce: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(NONCE_LENGTH)
+            .map(char::from)
+            .collect();
+        // Whole seconds, the precision kept in the challenge cookie, so
+        // `verify_challenge` rebuilds the exact message that was signed.
+        let issued_at = DateTime::from_timestamp(Utc::now().timestamp(), 0)
+            .expect("current time is a valid timestamp");
+
+        let message = self.sign_in_message(pubkey, &nonce, issued_at);
+        let value = format!("{}.{}.{}", pubkey, nonce, issued_at.timestamp());
+        let jar = PrivateCookieJar::new(self.key.clone()).add(self.cookie(CHALLENGE_COOKIE, value));
+        (jar, message)
+    }
+
+    /// Checks the base64 `signature` over the challenge stored in `jar` and
+    /// replaces the challenge with a session for `pubkey`.
+    pub fn verify_challenge(
+        &self,
+        jar: PrivateCookieJar,
+        pubkey: &Pubkey,
+        signature: &str,
+    ) -> Result<PrivateCookieJar, SignInError> {
+        let challenge = jar
+            .get(CHALLENGE_COOKIE)
+            .ok_or(SignInError::MissingChallenge)?;
+        let mut parts = challenge.value().split('.');
+        let (Some(challenge_pubkey), Some(nonce), Some(issued_at)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(SignInError::MissingChallenge);
+        };
+        if challenge_pubkey != pubkey.to_string() {
+            return Err(SignInError::MissingChallenge);
+        }
+        let issued_at = issued_at
+            .parse()
+            .ok()
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .ok_or(SignInError::MissingChallenge)?;
+        if Utc::now() - issued_at > Duration::minutes(CHALLENGE_TTL_MINUTES) {
+            return Err(SignInError::ChallengeExpired);
+        }
+
+        let message = self.sign_in_message(pubkey, nonce, issued_at);
+        let signature = BASE64
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::try_from(bytes.as_slice()).ok())
+            .ok_or(SignInError::InvalidSignature)?;
+        if !signature.verify(pubkey.as_ref(), message.as_bytes()) {
+            return Err(SignInError::InvalidSignature);
+        }
+
+        let expires_at = Utc::now() + Duration::hours(SESSION_TTL_HOURS);
+        let value = format!("{}.{}", pubkey, expires_at.timestamp());
+        Ok(jar
+            .remove(Cookie::build(CHALLENGE_COOKIE).path("/"))
+            .add(self.cookie(SESSION_COOKIE, value)))
+    }
+
+    pub fn sign_out(&self, jar: PrivateCookieJar) -> PrivateCookieJar {
+        jar.remove(Cookie::build(SESSION_COOKIE).path("/"))
+    }
+
+    fn session(&self, jar: &PrivateCookieJar) -> Option<WalletSession> {
+        let cookie = jar.get(SESSION_COOKIE)?;
+        let (pubkey, expires_at) = cookie.value().split_once('.')?;
+        if Utc::now().timestamp() > expires_at.parse::<i64>().ok()? {
+            return None;
+        }
+        Some(WalletSession {
+            pubkey: Pubkey::from_str(pubkey).ok()?,
+        })
+    }
+
+    /// Reads the challenge and session cookies sent with a request.
+    pub fn jar(&self, headers: &HeaderMap) -> PrivateCookieJar {
+        PrivateCookieJar::from_headers(headers, self.key.clone())
+    }
+}
+
+/// The wallet signed in on this request, taken from the session cookie.
+/// Handlers taking it reject requests without a session.
+#[derive(Debug, Clone, Copy)]
+pub struct WalletSession {
+    pub pubkey: Pubkey,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for WalletSession
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let config = parts.extensions.get::<SessionConfig>().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Sessions are not configured",
+        ))?;
+        config
+            .session(&config.jar(&parts.headers))
+            .ok_or((StatusCode::UNAUTHORIZED, "Sign in with your wallet first"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn sign(keypair: &Keypair, message: &str) -> String {
+        BASE64.encode(keypair.sign_message(message.as_bytes()).as_ref())
+    }
+
+    #[test]
+    fn signed_challenge_starts_a_session() {
+        let config = SessionConfig::new(None, "http://localhost:3000");
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+
+        let (jar, message) = config.issue_challenge(&pubkey);
+        let jar = config
+            .verify_challenge(jar, &pubkey, &sign(&keypair, &message))
+            .unwrap();
+
+        let session = config.session(&jar).unwrap();
+        assert_eq!(session.pubkey, pubkey);
+        assert!(jar.get(CHALLENGE_COOKIE).is_none());
+    }
+
+    #[test]
+    fn challenge_rejects_other_signers() {
+        let config = SessionConfig::new(None, "http://localhost:3000");
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+
+        let (jar, message) = config.issue_challenge(&pubkey);
+        assert_eq!(
+            config
+                .verify_challenge(jar.clone(), &pubkey, &sign(&Keypair::new(), &message))
+                .unwrap_err(),
+            SignInError::InvalidSignature
+        );
+        assert_eq!(
+            config
+                .verify_challenge(jar, &Keypair::new().pubkey(), &sign(&keypair, &message))
+                .unwrap_err(),
+            SignInError::MissingChallenge
+        );
+    }
+}
@@ -0,0 +1,487 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient, rpc_config::RpcSignatureSubscribeConfig,
+    rpc_response::RpcSignatureResult,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use tokio_stream::StreamExt;
+
+use crate::program_errors::{describe_transaction_error, transaction_logs};
+use crate::solana_rpc::SolanaRpc;
+use crate::solana_transactions_repository::SolanaTransaction;
+use crate::status_events::{TxStatusEvent, TxStatusSender};
+use crate::transaction_status::TransactionStatus;
+use crate::transaction_validation::decode_transaction;
+
+/// Delay between websocket reconnect attempts.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// How long a subscription waits for a notification before giving up. Anything
+/// still pending after this is left to the polling sweep.
+const SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// How long the socket may go without a slot notification before it is
+/// treated as dropped. Slots arrive several times a second on a live cluster.
+const CONNECTION_SILENCE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most pending transactions resubscribed after a reconnect.
+const MAX_RESUBSCRIBED_TRANSACTIONS: i64 = 4096;
+
+/// Which backend keeps `solana_transactions` statuses up to date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackingMode {
+    /// Poll `getSignatureStatuses` for every pending transaction.
+    Polling,
+    /// Use `signatureSubscribe`, falling back to polling while the socket is down.
+    Websocket,
+    /// Run both side by side.
+    Hybrid,
+}
+
+impl FromStr for TrackingMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "polling" => Ok(TrackingMode::Polling),
+            "websocket" => Ok(TrackingMode::Websocket),
+            "hybrid" => Ok(TrackingMode::Hybrid),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Derives the pubsub websocket url from an http(s) RPC url.
+pub fn websocket_url(rpc_url: &str) -> String {
+    if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        rpc_url.to_string()
+    }
+}
+
+/// Handle to the websocket tracking task. Cloned into handlers so newly sent
+/// signatures get subscribed right away.
+#[derive(Clone)]
+pub struct SignatureSubscriber {
+    sender: Option<mpsc::UnboundedSender<(i32, Signature)>>,
+    healthy: Arc<AtomicBool>,
+}
+
+impl SignatureSubscriber {
+    /// A subscriber that ignores signatures, used in polling mode.
+    pub fn disabled() -> Self {
+        SignatureSubscriber {
+            sender: None,
+            healthy: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Connects to `ws_url` in the background, reconnecting and resubscribing
+    /// every pending transaction whenever the socket drops.
+    pub fn spawn(
+        ws_url: String,
+        pool: Arc<deadpool_diesel::mysql::Pool>,
+        rpc_client: Arc<dyn SolanaRpc>,
+        status_events: TxStatusSender,
+    ) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<(i32, Signature)>();
+        let healthy = Arc::new(AtomicBool::new(false));
+
+        {
+            let healthy = healthy.clone();
+            tokio::spawn(async move {
+                loop {
+                    match PubsubClient::new(&ws_url).await {
+                        Ok(client) => {
+                            println!("Connected to pubsub websocket");
+                            let client = Arc::new(client);
+                            let (closed_sender, mut closed_receiver) =
+                                mpsc::unbounded_channel::<()>();
+                            healthy.store(true, Ordering::SeqCst);
+                            watch_connection(&client, &closed_sender);
+
+                            let txs = SolanaTransaction::get_all_not_finalized_or_failed(
+                                &pool,
+                                MAX_RESUBSCRIBED_TRANSACTIONS,
+                            )
+                            .await;
+                            if let Ok(txs) = txs {
+                                for tx in txs.iter() {
+                                    if let Some(sig) = tx
+                                        .tx_signature
+                                        .as_ref()
+                                        .and_then(|sig| Signature::from_str(sig).ok())
+                                    {
+                                        subscribe(
+                                            &client,
+                                            &pool,
+                                            &rpc_client,
+                                            &status_events,
+                                            &closed_sender,
+                                            tx.id,
+                                            sig,
+                                        );
+                                    }
+                                }
+                            }
+
+                            loop {
+                                tokio::select! {
+                                    next = receiver.recv() => match next {
+                                        Some((tx_id, sig)) => subscribe(
+                                            &client,
+                                            &pool,
+                                            &rpc_client,
+                                            &status_events,
+                                            &closed_sender,
+                                            tx_id,
+                                            sig,
+                                        ),
+                                        None => return,
+                                    },
+                                    _ = closed_receiver.recv() => break,
+                                }
+                            }
+
+                            println!("Pubsub websocket closed, falling back to polling");
+                            healthy.store(false, Ordering::SeqCst);
+                        }
+                        Err(err) => {
+                            println!("Failed to connect to pubsub websocket: {:?}", err);
+                            healthy.store(false, Ordering::SeqCst);
+                        }
+                    }
+                    sleep(RECONNECT_DELAY).await;
+                }
+            });
+        }
+
+        SignatureSubscriber {
+            sender: Some(sender),
+            healthy,
+        }
+    }
+
+    /// Starts tracking `signature` for the stored transaction `tx_id`.
+    pub fn subscribe(&self, tx_id: i32, signature: Signature) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send((tx_id, signature));
+        }
+    }
+
+    /// Whether the websocket is currently connected.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+}
+
+/// Signals `closed` when the socket drops or goes quiet, so a lost connection
+/// is noticed even with no signature subscriptions open.
+fn watch_connection(client: &Arc<PubsubClient>, closed: &mpsc::UnboundedSender<()>) {
+    let client = client.clone();
+    let closed = closed.clone();
+    tokio::spawn(async move {
+        if let Ok((mut slots, _unsubscribe)) = client.slot_subscribe().await {
+            while let Ok(Some(_)) = timeout(CONNECTION_SILENCE_TIMEOUT, slots.next()).await {}
+        }
+        let _ = closed.send(());
+    });
+}
+
+/// Subscribes to `sig` at Confirmed and Finalized commitment and writes each
+/// notification to the database. Signals `closed` when the socket is gone.
+fn subscribe(
+    client: &Arc<PubsubClient>,
+    pool: &Arc<deadpool_diesel::mysql::Pool>,
+    rpc_client: &Arc<dyn SolanaRpc>,
+    status_events: &TxStatusSender,
+    closed: &mpsc::UnboundedSender<()>,
+    tx_id: i32,
+    sig: Signature,
+) {
+    for commitment in [CommitmentConfig::confirmed(), CommitmentConfig::finalized()] {
+        let client = client.clone();
+        let pool = pool.clone();
+        let rpc_client = rpc_client.clone();
+        let status_events = status_events.clone();
+        let closed = closed.clone();
+        tokio::spawn(async move {
+            let config = RpcSignatureSubscribeConfig {
+                commitment: Some(commitment),
+                enable_received_notification: Some(false),
+            };
+            let (mut notifications, _unsubscribe) =
+                match client.signature_subscribe(&sig, Some(config)).await {
+                    Ok(subscription) => subscription,
+                    Err(_) => {
+                        let _ = closed.send(());
+                        return;
+                    }
+                };
+
+            match timeout(SUBSCRIPTION_TIMEOUT, notifications.next()).await {
+                Ok(Some(notification)) => {
+                    let error = match notification.value {
+                        RpcSignatureResult::ProcessedSignature(result) => result.err,
+                        RpcSignatureResult::ReceivedSignature(_) => None,
+                    };
+                    let tx = SolanaTransaction::get_by_id(&pool, tx_id).await;
+                    if let Ok(tx) = tx {
+                        let mut error_message = None;
+                        let update = if let Some(error) = error {
+                            // Landed but failed; whichever notification arrives first records it.
+                            if tx.status.can_transition_to(TransactionStatus::Failed) {
+                                let logs = transaction_logs(rpc_client.as_ref(), &sig).await;
+                                let message = describe_transaction_error(
+                                    &error,
+                                    decode_transaction(&tx.tx).as_ref(),
+                                    &logs,
+                                );
+                                error_message = Some(message.clone());
+                                Some((
+                                    SolanaTransaction::set_status_failed(
+                                        &pool, tx_id, error, message,
+                                    )
+                                    .await,
+                                    TransactionStatus::Failed,
+                                ))
+                            } else {
+                                None
+                            }
+                        } else if commitment == CommitmentConfig::finalized()
+                            && tx.status.can_transition_to(TransactionStatus::Finalized)
+                        {
+                            Some((
+                                SolanaTransaction::set_status_finalized(&pool, tx_id).await,
+                                TransactionStatus::Finalized,
+                            ))
+                        } else if commitment == CommitmentConfig::confirmed()
+                            && tx.status.can_transition_to(TransactionStatus::Confirmed)
+                        {
+                            Some((
+                                SolanaTransaction::set_status_confirmed(&pool, tx_id).await,
+                                TransactionStatus::Confirmed,
+                            ))
+                        } else {
+                            None
+                        };
+                        if let Some((db_result, status)) = update {
+                            if let Err(err) = db_result {
+                                println!("Failed to update tx {} status: {}", tx_id, err);
+                                return;
+                            }
+                            let _ = status_events.send(TxStatusEvent {
+                                tx_id,
+                                signature: Some(sig.to_string()),
+                                status,
+                                error: error_message,
+                            });
+                        }
+                    }
+                }
+                Ok(None) => {
+                    let _ = closed.send(());
+                }
+                Err(_) => {}
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solana_rpc::MockRpc;
+    use crate::solana_transactions_repository::NewSolanaTransaction;
+    use crate::status_events;
+    use deadpool_diesel::mysql::{Manager, Pool};
+    use futures_util::SinkExt;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+    /// A pubsub endpoint that acknowledges `signatureSubscribe` requests,
+    /// streams slots to a `slotSubscribe` and drops the connection, without a
+    /// close frame, when told to.
+    struct StubPubsub {
+        url: String,
+        subscriptions: mpsc::UnboundedReceiver<String>,
+        drop_connection: mpsc::UnboundedSender<()>,
+    }
+
+    impl StubPubsub {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}", listener.local_addr().unwrap());
+            let (subscription_sender, subscriptions) = mpsc::unbounded_channel();
+            let (drop_connection, mut drop_receiver) = mpsc::unbounded_channel::<()>();
+            tokio::spawn(async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let mut ws = accept_async(stream).await.unwrap();
+                    let mut slot_subscription = None;
+                    let mut slots = tokio::time::interval(Duration::from_millis(100));
+                    loop {
+                        tokio::select! {
+                            message = ws.next() => {
+                                let Some(Ok(Message::Text(text))) = message else {
+                                    break;
+                                };
+                                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                                match request["method"].as_str() {
+                                    Some("signatureSubscribe") => {
+                                        let signature = request["params"][0].as_str().unwrap();
+                                        let _ = subscription_sender.send(signature.to_string());
+                                    }
+                                    Some("slotSubscribe") => slot_subscription = Some(request["id"].clone()),
+                                    _ => continue,
+                                }
+                                let response = serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "result": request["id"],
+                                    "id": request["id"],
+                                });
+                                ws.send(Message::Text(response.to_string())).await.unwrap();
+                            }
+                            _ = slots.tick(), if slot_subscription.is_some() => {
+                                let notification = serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "method": "slotNotification",
+                                    "params": {
+                                        "result": {"parent": 1, "root": 0, "slot": 2},
+                                        "subscription": slot_subscription,
+                                    },
+                                });
+                                if ws.send(Message::Text(notification.to_string())).await.is_err() {
+                                    break;
+                                }
+                            }
+                            _ = drop_receiver.recv() => break,
+                        }
+                    }
+                }
+            });
+            StubPubsub {
+                url,
+                subscriptions,
+                drop_connection,
+            }
+        }
+
+        /// Waits for a `signatureSubscribe` for `signature`, skipping others.
+        async fn expect_subscription(&mut self, signature: &Signature) {
+            timeout(Duration::from_secs(10), async {
+                while self.subscriptions.recv().await.unwrap() != signature.to_string() {}
+            })
+            .await
+            .expect("no signatureSubscribe for the signature");
+        }
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        timeout(Duration::from_secs(10), async {
+            while !condition() {
+                sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("condition not met in time");
+    }
+
+    fn pool(database_url: &str) -> Arc<Pool> {
+        let manager = Manager::new(database_url, deadpool_diesel::Runtime::Tokio1);
+        Arc::new(Pool::builder(manager).build().unwrap())
+    }
+
+    #[tokio::test]
+    async fn reports_unhealthy_until_reconnected() {
+        let mut pubsub = StubPubsub::start().await;
+        // Nothing to resubscribe from the database.
+        let subscriber = SignatureSubscriber::spawn(
+            pubsub.url.clone(),
+            pool("mysql://127.0.0.1:1/unreachable"),
+            Arc::new(MockRpc::new()),
+            status_events::channel(),
+        );
+        wait_until(|| subscriber.is_healthy()).await;
+
+        let signature = Signature::new_unique();
+        subscriber.subscribe(1, signature);
+        pubsub.expect_subscription(&signature).await;
+
+        pubsub.drop_connection.send(()).unwrap();
+        wait_until(|| !subscriber.is_healthy()).await;
+        wait_until(|| subscriber.is_healthy()).await;
+    }
+
+    #[tokio::test]
+    async fn reports_unhealthy_without_open_subscriptions() {
+        let pubsub = StubPubsub::start().await;
+        let subscriber = SignatureSubscriber::spawn(
+            pubsub.url.clone(),
+            pool("mysql://127.0.0.1:1/unreachable"),
+            Arc::new(MockRpc::new()),
+            status_events::channel(),
+        );
+        wait_until(|| subscriber.is_healthy()).await;
+        // Let the connection watcher subscribe before the socket goes.
+        sleep(Duration::from_millis(300)).await;
+
+        pubsub.drop_connection.send(()).unwrap();
+        wait_until(|| !subscriber.is_healthy()).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn resubscribes_pending_transactions_after_reconnect() {
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL not set in env.");
+        let pool = pool(&database_url);
+        let now = chrono::Utc::now().naive_utc();
+        let id = SolanaTransaction::insert(
+            &pool,
+            NewSolanaTransaction::fixture(
+                solana_sdk::pubkey::Pubkey::new_unique().to_string(),
+                u64::MAX,
+            ),
+        )
+        .await
+        .unwrap();
+        let signature = Signature::new_unique();
+        SolanaTransaction::set_status_sent(&pool, id, signature.to_string(), now, String::new())
+            .await
+            .unwrap();
+
+        let mut pubsub = StubPubsub::start().await;
+        let subscriber = SignatureSubscriber::spawn(
+            pubsub.url.clone(),
+            pool.clone(),
+            Arc::new(MockRpc::new()),
+            status_events::channel(),
+        );
+        pubsub.expect_subscription(&signature).await;
+        assert!(subscriber.is_healthy());
+
+        pubsub.drop_connection.send(()).unwrap();
+        wait_until(|| !subscriber.is_healthy()).await;
+        pubsub.expect_subscription(&signature).await;
+        wait_until(|| subscriber.is_healthy()).await;
+
+        let conn = pool.get().await.unwrap();
+        conn.interact(move |conn| {
+            use diesel::prelude::*;
+            diesel::delete(crate::schema::solana_transactions::dsl::solana_transactions.find(id))
+                .execute(conn)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    }
+}
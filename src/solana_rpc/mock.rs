@@ -0,0 +1,330 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig, RpcTransactionConfig},
+    rpc_request::{RpcError, RpcResponseErrorData},
+    rpc_response::{
+        Response, RpcConfirmedTransactionStatusWithSignature, RpcPrioritizationFee,
+        RpcResponseContext, RpcResult, RpcSimulateTransactionResult,
+    },
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    epoch_info::EpochInfo,
+    hash::Hash,
+    message::Message,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+    EncodedTransactionWithStatusMeta, TransactionBinaryEncoding, TransactionConfirmationStatus,
+    TransactionStatus as SignatureStatus,
+};
+
+use super::SolanaRpc;
+
+/// Blocks a blockhash stays valid for, as on mainnet.
+const BLOCKHASH_VALIDITY: u64 = 150;
+
+/// Blocks after landing until a transaction is rooted.
+const BLOCKS_TO_FINALIZE: u64 = 32;
+
+/// An in-process cluster for driving the server without a network.
+///
+/// Sent transactions land at the current block height and move from
+/// Processed to Confirmed one block later and to Finalized after
+/// `BLOCKS_TO_FINALIZE` blocks, as `advance_block_height` is called.
+pub struct MockRpc {
+    state: Mutex<MockState>,
+}
+
+struct MockState {
+    block_height: u64,
+    blockhash: Hash,
+    landed: HashMap<Signature, LandedTransaction>,
+    drop_transactions: bool,
+    failing_calls: usize,
+    calls: usize,
+    simulation: RpcSimulateTransactionResult,
+    fee: u64,
+}
+
+struct LandedTransaction {
+    block_height: u64,
+    err: Option<TransactionError>,
+    tx: Transaction,
+}
+
+impl Default for MockRpc {
+    fn default() -> Self {
+        MockRpc {
+            state: Mutex::new(MockState {
+                block_height: 1,
+                blockhash: Hash::new_unique(),
+                landed: HashMap::new(),
+                drop_transactions: false,
+                failing_calls: 0,
+                calls: 0,
+                simulation: RpcSimulateTransactionResult {
+                    err: None,
+                    logs: Some(Vec::new()),
+                    accounts: None,
+                    units_consumed: Some(1_000),
+                    return_data: None,
+                    inner_instructions: None,
+                },
+                fee: 5_000,
+            }),
+        }
+    }
+}
+
+impl MockRpc {
+    pub fn new() -> Self {
+        MockRpc::default()
+    }
+
+    pub fn block_height(&self) -> u64 {
+        self.state.lock().unwrap().block_height
+    }
+
+    /// Produces `blocks` blocks with a fresh blockhash.
+    pub fn advance_block_height(&self, blocks: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.block_height += blocks;
+        state.blockhash = Hash::new_unique();
+    }
+
+    /// While set, sends succeed but the transactions never land.
+    pub fn set_drop_transactions(&self, drop_transactions: bool) {
+        self.state.lock().unwrap().drop_transactions = drop_transactions;
+    }
+
+    /// Makes the next `calls` RPC calls of any kind fail.
+    pub fn fail_next_calls(&self, calls: usize) {
+        self.state.lock().unwrap().failing_calls = calls;
+    }
+
+    /// RPC calls made so far, failed ones included.
+    pub fn calls(&self) -> usize {
+        self.state.lock().unwrap().calls
+    }
+
+    /// Result returned by simulations, and checked by preflight on send.
+    pub fn set_simulation(&self, simulation: RpcSimulateTransactionResult) {
+        self.state.lock().unwrap().simulation = simulation;
+    }
+
+    pub fn set_fee(&self, fee: u64) {
+        self.state.lock().unwrap().fee = fee;
+    }
+
+    /// Marks a landed transaction as failed during execution.
+    pub fn fail_transaction(&self, signature: &Signature, err: TransactionError) {
+        if let Some(landed) = self.state.lock().unwrap().landed.get_mut(signature) {
+            landed.err = Some(err);
+        }
+    }
+
+    /// Locks the state, spending one scripted failure if any are left.
+    fn call(&self) -> Result<MutexGuard<'_, MockState>, MockFailure> {
+        let mut state = self.state.lock().unwrap();
+        state.calls += 1;
+        if state.failing_calls > 0 {
+            state.failing_calls -= 1;
+            return Err(MockFailure);
+        }
+        Ok(state)
+    }
+}
+
+/// A scripted failure, returned to callers as a custom client error.
+struct MockFailure;
+
+impl From<MockFailure> for ClientError {
+    fn from(_: MockFailure) -> Self {
+        ClientErrorKind::Custom("Mock RPC failure".to_string()).into()
+    }
+}
+
+impl MockState {
+    fn response<T>(&self, value: T) -> Response<T> {
+        Response {
+            context: RpcResponseContext {
+                slot: self.block_height,
+                api_version: None,
+            },
+            value,
+        }
+    }
+
+    fn signature_status(&self, signature: &Signature) -> Option<SignatureStatus> {
+        let landed = self.landed.get(signature)?;
+        let blocks = self.block_height - landed.block_height;
+        let (confirmations, confirmation_status) = if blocks >= BLOCKS_TO_FINALIZE {
+            (None, TransactionConfirmationStatus::Finalized)
+        } else if blocks > 0 {
+            (
+                Some(blocks as usize),
+                TransactionConfirmationStatus::Confirmed,
+            )
+        } else {
+            (Some(0), TransactionConfirmationStatus::Processed)
+        };
+        Some(SignatureStatus {
+            slot: landed.block_height,
+            confirmations,
+            status: landed.err.clone().map_or(Ok(()), Err),
+            err: landed.err.clone(),
+            confirmation_status: Some(confirmation_status),
+        })
+    }
+}
+
+#[async_trait]
+impl SolanaRpc for MockRpc {
+    async fn get_latest_blockhash_with_commitment(
+        &self,
+        _commitment: CommitmentConfig,
+    ) -> ClientResult<(Hash, u64)> {
+        let state = self.call()?;
+        Ok((state.blockhash, state.block_height + BLOCKHASH_VALIDITY))
+    }
+
+    async fn send_transaction_with_config(
+        &self,
+        tx: &Transaction,
+        config: RpcSendTransactionConfig,
+    ) -> ClientResult<Signature> {
+        let mut state = self.call()?;
+        if !config.skip_preflight && state.simulation.err.is_some() {
+            return Err(ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                code: -32002,
+                message: "Transaction simulation failed".to_string(),
+                data: RpcResponseErrorData::SendTransactionPreflightFailure(
+                    state.simulation.clone(),
+                ),
+            })
+            .into());
+        }
+
+        let signature = tx.signatures.first().copied().unwrap_or_default();
+        if !state.drop_transactions && !state.landed.contains_key(&signature) {
+            let landed = LandedTransaction {
+                block_height: state.block_height,
+                err: state.simulation.err.clone(),
+                tx: tx.clone(),
+            };
+            state.landed.insert(signature, landed);
+        }
+        Ok(signature)
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<SignatureStatus>>> {
+        let state = self.call()?;
+        let statuses = signatures
+            .iter()
+            .map(|signature| state.signature_status(signature))
+            .collect();
+        Ok(state.response(statuses))
+    }
+
+    async fn get_epoch_info(&self) -> ClientResult<EpochInfo> {
+        let state = self.call()?;
+        Ok(EpochInfo {
+            epoch: 0,
+            slot_index: state.block_height,
+            slots_in_epoch: 432_000,
+            absolute_slot: state.block_height,
+            block_height: state.block_height,
+            transaction_count: Some(state.landed.len() as u64),
+        })
+    }
+
+    async fn simulate_transaction_with_config(
+        &self,
+        _tx: &Transaction,
+        _config: RpcSimulateTransactionConfig,
+    ) -> RpcResult<RpcSimulateTransactionResult> {
+        let state = self.call()?;
+        Ok(state.response(state.simulation.clone()))
+    }
+
+    async fn get_fee_for_message(&self, message: &Message) -> ClientResult<u64> {
+        let state = self.call()?;
+        Ok(state.fee * message.header.num_required_signatures as u64)
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ClientResult<Vec<RpcPrioritizationFee>> {
+        let _state = self.call()?;
+        Ok(Vec::new())
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        _config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        let state = self.call()?;
+        let landed = state.landed.get(signature).ok_or_else(|| {
+            ClientError::from(ClientErrorKind::Custom(format!(
+                "Mock RPC has no transaction {}",
+                signature
+            )))
+        })?;
+        let serialized_tx = bincode::serialize(&landed.tx).expect("transaction serializes");
+        // Without status meta, so logs come back empty.
+        Ok(EncodedConfirmedTransactionWithStatusMeta {
+            slot: landed.block_height,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::Binary(
+                    BASE64.encode(serialized_tx),
+                    TransactionBinaryEncoding::Base64,
+                ),
+                meta: None,
+                version: None,
+            },
+            block_time: None,
+        })
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let state = self.call()?;
+        let mut signatures: Vec<_> = state
+            .landed
+            .iter()
+            .filter(|(_, landed)| landed.tx.message.account_keys.contains(address))
+            .map(
+                |(signature, landed)| RpcConfirmedTransactionStatusWithSignature {
+                    signature: signature.to_string(),
+                    slot: landed.block_height,
+                    err: landed.err.clone(),
+                    memo: None,
+                    block_time: None,
+                    confirmation_status: state
+                        .signature_status(signature)
+                        .and_then(|status| status.confirmation_status),
+                },
+            )
+            .collect();
+        signatures.sort_by_key(|status| Reverse(status.slot));
+        Ok(signatures)
+    }
+}
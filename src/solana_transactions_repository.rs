@@ -1,8 +1,15 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::NaiveDateTime;
 use diesel::mysql::MysqlConnection;
 use diesel::prelude::*;
 use diesel::sql_types::{BigInt, Datetime, Integer, Nullable, SmallInt, Text, Unsigned};
 use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, transaction::TransactionError};
+
+use crate::repository_error::{RepositoryError, RepositoryResult};
+use crate::schema::solana_transactions::dsl;
+use crate::transaction_status::TransactionStatus;
+
+sql_function!(fn last_insert_id() -> Unsigned<BigInt>);
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Insertable, QueryableByName)]
 #[diesel(table_name = crate::schema::solana_transactions)]
@@ -10,10 +17,70 @@ use serde::{Deserialize, Serialize};
 pub struct NewSolanaTransaction {
     pub blockhash: String,
     pub last_valid_block_height: u64,
-    pub status: u16,
+    pub status: TransactionStatus,
     pub tx: String,
     pub created_at: NaiveDateTime,
     pub sent_at: Option<NaiveDateTime>,
+    pub priority_fee: Option<u32>,
+    /// Fee payer the transaction was built for.
+    pub owner: String,
+    /// [`crate::transaction_builder::TxType`] the transaction was built as.
+    pub tx_type: String,
+    /// Program the transaction's main instruction calls.
+    pub program_id: String,
+    /// Inputs the transaction was built from, see `TxType::params`.
+    pub params: Option<serde_json::Value>,
+    /// [`crate::transaction_builder::TxSource`] that requested the transaction.
+    pub source: String,
+    pub reference: Option<String>,
+    /// Network fee in lamports, base and priority, quoted when built.
+    pub fee: Option<u64>,
+}
+
+#[cfg(any(test, feature = "mock-rpc"))]
+impl NewSolanaTransaction {
+    /// An unsigned initialize row for `owner`, told apart from others by a
+    /// fresh blockhash, for tests that need rows without building them.
+    pub fn fixture(owner: String, last_valid_block_height: u64) -> Self {
+        NewSolanaTransaction {
+            blockhash: solana_sdk::hash::Hash::new_unique().to_string(),
+            last_valid_block_height,
+            status: TransactionStatus::Created,
+            tx: String::new(),
+            created_at: chrono::Utc::now().naive_utc(),
+            sent_at: None,
+            priority_fee: None,
+            owner,
+            tx_type: "initialize".to_string(),
+            program_id: String::new(),
+            params: None,
+            source: String::new(),
+            reference: None,
+            fee: None,
+        }
+    }
+}
+
+/// A status transition observed by the tracker, written in bulk by
+/// [`SolanaTransaction::apply_status_changes`].
+#[derive(Debug, Clone)]
+pub struct StatusChange {
+    pub id: i32,
+    pub status: TransactionStatus,
+    pub at: NaiveDateTime,
+    /// Execution error for transactions that landed but failed.
+    pub error: Option<TransactionError>,
+    pub error_message: Option<String>,
+}
+
+/// What [`SolanaTransaction::apply_status_changes`] wrote.
+#[derive(Debug)]
+pub struct AppliedStatusChanges {
+    /// The changes the rows' statuses still allowed.
+    pub changes: Vec<StatusChange>,
+    /// Statements sent: BEGIN, the locking SELECT, one UPDATE per applied
+    /// change and COMMIT.
+    pub statements: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
@@ -23,7 +90,7 @@ pub struct SolanaTransaction {
     pub id: i32,
     pub blockhash: String,
     pub last_valid_block_height: u64,
-    pub status: u16,
+    pub status: TransactionStatus,
     pub tx: String,
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
@@ -33,110 +100,395 @@ pub struct SolanaTransaction {
     pub time_to_send: Option<u32>,
     pub time_to_confirmed: Option<u32>,
     pub time_to_finalized: Option<u32>,
+    /// Compute unit price in micro-lamports.
     pub priority_fee: Option<u32>,
     pub tx_signature: Option<String>,
+    pub send_attempts: u32,
+    pub last_resent_at: Option<NaiveDateTime>,
+    /// JSON-serialized `TransactionError` when the transaction failed on chain.
+    pub tx_error: Option<String>,
+    pub failed_instruction_index: Option<u8>,
+    /// `tx_error` decoded against t-vault and Anchor error codes.
+    pub tx_error_message: Option<String>,
+    /// Wallet that requested the transaction. Only it may submit it or read its status.
+    pub owner: String,
+    pub tx_type: String,
+    pub program_id: String,
+    pub params: Option<serde_json::Value>,
+    /// [`crate::transaction_builder::TxSource`] that requested the transaction,
+    /// empty for rows stored before it was recorded.
+    pub source: String,
+    /// Solana Pay reference account the transaction is found by once the
+    /// wallet broadcasts it.
+    pub reference: Option<String>,
+    /// Network fee in lamports, base and priority, quoted when built.
+    pub fee: Option<u64>,
+}
+
+/// Optional filters for [`SolanaTransaction::get_page_by_owner`].
+#[derive(Debug, Clone, Default)]
+pub struct TransactionFilter {
+    pub status: Option<TransactionStatus>,
+    pub tx_type: Option<String>,
+    pub program_id: Option<String>,
+    /// Inclusive lower bound on `created_at`.
+    pub created_from: Option<NaiveDateTime>,
+    /// Exclusive upper bound on `created_at`.
+    pub created_before: Option<NaiveDateTime>,
+}
+
+/// Outcome counts for one transaction type, from
+/// [`SolanaTransaction::get_stats_by_type`].
+#[derive(Debug, Serialize, QueryableByName)]
+pub struct TxTypeStats {
+    #[diesel(sql_type = Text)]
+    pub tx_type: String,
+    #[diesel(sql_type = BigInt)]
+    pub total: i64,
+    /// Confirmed or finalized.
+    #[diesel(sql_type = BigInt)]
+    pub succeeded: i64,
+    #[diesel(sql_type = BigInt)]
+    pub failed: i64,
+    #[diesel(sql_type = BigInt)]
+    pub expired: i64,
+    /// Milliseconds from send to confirmation; `None` when none confirmed.
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub median_time_to_confirmed: Option<u64>,
+}
+
+impl TxTypeStats {
+    /// Share of settled transactions that succeeded. Transactions still in
+    /// flight are not counted.
+    pub fn success_rate(&self) -> Option<f64> {
+        let settled = self.succeeded + self.failed + self.expired;
+        (settled > 0).then(|| self.succeeded as f64 / settled as f64)
+    }
+}
+
+/// One page of a wallet's transactions, newest first.
+#[derive(Debug)]
+pub struct TransactionPage {
+    pub transactions: Vec<SolanaTransaction>,
+    /// Pass as `cursor` to get the next page; `None` on the last page.
+    pub next_cursor: Option<i32>,
 }
 
 impl SolanaTransaction {
+    /// The on-chain execution error, if the transaction landed and failed.
+    pub fn transaction_error(&self) -> Option<TransactionError> {
+        self.tx_error
+            .as_ref()
+            .and_then(|tx_error| serde_json::from_str(tx_error).ok())
+    }
+
+    pub fn is_owned_by(&self, pubkey: &Pubkey) -> bool {
+        self.owner == pubkey.to_string()
+    }
+
+    /// Human-readable execution error, decoded when it was recorded.
+    pub fn error_message(&self) -> Option<String> {
+        self.tx_error_message
+            .clone()
+            .or_else(|| self.transaction_error().map(|err| err.to_string()))
+    }
+
     pub async fn insert(
         pool: &deadpool_diesel::mysql::Pool,
         new_tx: NewSolanaTransaction,
-    ) -> Result<i32, ()> {
-        let conn = pool.get().await;
-        if let Ok(conn) = conn {
-            let res = conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("INSERT INTO solana_transactions (blockhash, last_valid_block_height, status, tx, created_at, sent_at) VALUES (?, ?, ?, ?, ?, ?)")
-                    .bind::<Text, _>(&new_tx.blockhash)
-                    .bind::<Unsigned<BigInt>, _>(&new_tx.last_valid_block_height)
-                    .bind::<Unsigned<SmallInt>, _>(new_tx.status)
-                    .bind::<Text, _>(&new_tx.tx)
-                    .bind::<Datetime, _>(new_tx.created_at)
-                    .bind::<Nullable<Datetime>, _>(new_tx.sent_at)
-                    .execute(conn)
-                    .expect("Error inserting new transaction");
+    ) -> RepositoryResult<i32> {
+        let conn = pool.get().await?;
+        // LAST_INSERT_ID is per connection, so reading it on the connection
+        // that ran the INSERT returns this row's id even under concurrent inserts.
+        let id = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                conn.transaction::<_, RepositoryError, _>(|conn| {
+                    diesel::insert_into(dsl::solana_transactions)
+                        .values(&new_tx)
+                        .execute(conn)?;
 
-                diesel::sql_query("SELECT * FROM solana_transactions ORDER BY id DESC LIMIT 1")
-                    .get_result::<SolanaTransaction>(conn)
+                    // `id` is an INT column, so the generated value always fits.
+                    let id = diesel::select(last_insert_id()).get_result::<u64>(conn)?;
+                    Ok(id as i32)
+                })
             })
-            .await;
+            .await??;
 
-            if let Ok(Ok(res)) = res {
-                return Ok(res.id);
-            }
-
-            Err(())
-        } else {
-            Err(())
-        }
+        Ok(id)
     }
 
     pub async fn get_by_id(
         pool: &deadpool_diesel::mysql::Pool,
         id: i32,
-    ) -> Result<SolanaTransaction, ()> {
-        let conn = pool.get().await;
-        if let Ok(conn) = conn {
-            let res = conn
-                .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("SELECT * FROM solana_transactions WHERE id = ?")
-                        .bind::<Integer, _>(id)
-                        .get_result::<SolanaTransaction>(conn)
-                })
-                .await;
+    ) -> RepositoryResult<SolanaTransaction> {
+        let conn = pool.get().await?;
+        let transaction = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT * FROM solana_transactions WHERE id = ?")
+                    .bind::<Integer, _>(id)
+                    .get_result::<SolanaTransaction>(conn)
+            })
+            .await??;
+
+        Ok(transaction)
+    }
+
+    /// Like [`SolanaTransaction::get_by_id`], but `NotFound` unless `owner` requested it.
+    pub async fn get_by_id_for_owner(
+        pool: &deadpool_diesel::mysql::Pool,
+        id: i32,
+        owner: String,
+    ) -> RepositoryResult<SolanaTransaction> {
+        let conn = pool.get().await?;
+        let transaction = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT * FROM solana_transactions WHERE id = ? AND owner = ?")
+                    .bind::<Integer, _>(id)
+                    .bind::<Text, _>(&owner)
+                    .get_result::<SolanaTransaction>(conn)
+            })
+            .await??;
+
+        Ok(transaction)
+    }
+
+    /// Transactions built for `owner` with an id below `cursor`, newest first.
+    pub async fn get_page_by_owner(
+        pool: &deadpool_diesel::mysql::Pool,
+        owner: String,
+        filter: TransactionFilter,
+        cursor: Option<i32>,
+        limit: i64,
+    ) -> RepositoryResult<TransactionPage> {
+        let conn = pool.get().await?;
+        let mut transactions = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                let mut query = dsl::solana_transactions
+                    .filter(dsl::owner.eq(owner))
+                    .into_boxed();
+                if let Some(cursor) = cursor {
+                    query = query.filter(dsl::id.lt(cursor));
+                }
+                if let Some(status) = filter.status {
+                    query = query.filter(dsl::status.eq(status));
+                }
+                if let Some(tx_type) = filter.tx_type {
+                    query = query.filter(dsl::tx_type.eq(tx_type));
+                }
+                if let Some(program_id) = filter.program_id {
+                    query = query.filter(dsl::program_id.eq(program_id));
+                }
+                if let Some(created_from) = filter.created_from {
+                    query = query.filter(dsl::created_at.ge(created_from));
+                }
+                if let Some(created_before) = filter.created_before {
+                    query = query.filter(dsl::created_at.lt(created_before));
+                }
+
+                // One extra row tells whether there is a next page.
+                query
+                    .order(dsl::id.desc())
+                    .limit(limit + 1)
+                    .select(SolanaTransaction::as_select())
+                    .load::<SolanaTransaction>(conn)
+            })
+            .await??;
 
-            match res {
-                Ok(Ok(transaction)) => Ok(transaction),
-                _ => Err(()),
-            }
+        let next_cursor = if transactions.len() as i64 > limit {
+            transactions.truncate(limit as usize);
+            transactions.last().map(|tx| tx.id)
         } else {
-            Err(())
-        }
+            None
+        };
+        Ok(TransactionPage {
+            transactions,
+            next_cursor,
+        })
+    }
+
+    /// Outcome counts and median confirmation time for every transaction type.
+    pub async fn get_stats_by_type(
+        pool: &deadpool_diesel::mysql::Pool,
+    ) -> RepositoryResult<Vec<TxTypeStats>> {
+        let conn = pool.get().await?;
+        let stats = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                // The median is the middle row, or the mean of the middle two,
+                // of each type's confirmation times.
+                diesel::sql_query(
+                    "SELECT counts.tx_type, counts.total, counts.succeeded, counts.failed, \
+                       counts.expired, medians.median_time_to_confirmed \
+                     FROM ( \
+                       SELECT tx_type, COUNT(*) AS total, \
+                         CAST(SUM(status IN (?, ?)) AS SIGNED) AS succeeded, \
+                         CAST(SUM(status = ?) AS SIGNED) AS failed, \
+                         CAST(SUM(status = ?) AS SIGNED) AS expired \
+                       FROM solana_transactions GROUP BY tx_type \
+                     ) counts LEFT JOIN ( \
+                       SELECT tx_type, \
+                         CAST(ROUND(AVG(time_to_confirmed)) AS UNSIGNED) AS median_time_to_confirmed \
+                       FROM ( \
+                         SELECT tx_type, time_to_confirmed, \
+                           ROW_NUMBER() OVER (PARTITION BY tx_type ORDER BY time_to_confirmed) AS row_index, \
+                           COUNT(*) OVER (PARTITION BY tx_type) AS row_total \
+                         FROM solana_transactions WHERE time_to_confirmed IS NOT NULL \
+                       ) ranked \
+                       WHERE row_index IN (FLOOR((row_total + 1) / 2), FLOOR((row_total + 2) / 2)) \
+                       GROUP BY tx_type \
+                     ) medians ON medians.tx_type = counts.tx_type \
+                     ORDER BY counts.tx_type",
+                )
+                .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Confirmed)
+                .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Finalized)
+                .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Failed)
+                .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Expired)
+                .load::<TxTypeStats>(conn)
+            })
+            .await??;
+
+        Ok(stats)
     }
 
     pub async fn get_by_signature(
         pool: &deadpool_diesel::mysql::Pool,
         signature: String,
-    ) -> Result<SolanaTransaction, ()> {
-        let conn = pool.get().await;
-        if let Ok(conn) = conn {
-            let res = conn
-                .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("SELECT * FROM solana_transactions WHERE tx_signature = ?")
-                        .bind::<Text, _>(&signature)
-                        .get_result::<SolanaTransaction>(conn)
-                })
-                .await;
+    ) -> RepositoryResult<SolanaTransaction> {
+        let conn = pool.get().await?;
+        let transaction = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT * FROM solana_transactions WHERE tx_signature = ?")
+                    .bind::<Text, _>(&signature)
+                    .get_result::<SolanaTransaction>(conn)
+            })
+            .await??;
 
-            match res {
-                Ok(Ok(transaction)) => Ok(transaction),
-                _ => Err(()),
-            }
-        } else {
-            Err(())
-        }
+        Ok(transaction)
+    }
+
+    /// Like [`SolanaTransaction::get_by_signature`], but `NotFound` unless `owner` requested it.
+    pub async fn get_by_signature_for_owner(
+        pool: &deadpool_diesel::mysql::Pool,
+        signature: String,
+        owner: String,
+    ) -> RepositoryResult<SolanaTransaction> {
+        let conn = pool.get().await?;
+        let transaction = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query(
+                    "SELECT * FROM solana_transactions WHERE tx_signature = ? AND owner = ?",
+                )
+                .bind::<Text, _>(&signature)
+                .bind::<Text, _>(&owner)
+                .get_result::<SolanaTransaction>(conn)
+            })
+            .await??;
+
+        Ok(transaction)
+    }
+
+    /// Unsent transactions with a Solana Pay reference built after
+    /// `created_after`, newest first so a burst of abandoned requests cannot
+    /// hold back the ones being paid.
+    pub async fn get_all_pending_references(
+        pool: &deadpool_diesel::mysql::Pool,
+        created_after: NaiveDateTime,
+        limit: i64,
+    ) -> RepositoryResult<Vec<SolanaTransaction>> {
+        let conn = pool.get().await?;
+        let transactions = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT * FROM solana_transactions WHERE status = ? AND reference IS NOT NULL AND created_at > ? ORDER BY id DESC LIMIT ?")
+                    .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Created)
+                    .bind::<Datetime, _>(created_after)
+                    .bind::<BigInt, _>(limit)
+                    .load::<SolanaTransaction>(conn)
+            })
+            .await??;
+
+        Ok(transactions)
     }
 
     pub async fn get_all_not_finalized_or_failed(
         pool: &deadpool_diesel::mysql::Pool,
-    ) -> Result<Vec<SolanaTransaction>, ()> {
-        let conn = pool.get().await;
-        if let Ok(conn) = conn {
-            let res = conn
-                .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("SELECT * FROM solana_transactions WHERE status < 4 AND sent_at IS NOT NULL LIMIT 255")
-                        .load::<SolanaTransaction>(conn)
-                })
-                .await;
+        limit: i64,
+    ) -> RepositoryResult<Vec<SolanaTransaction>> {
+        let conn = pool.get().await?;
+        let transactions = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                // Statuses are ordered, so this is every sent row short of Finalized.
+                diesel::sql_query("SELECT * FROM solana_transactions WHERE status < ? AND sent_at IS NOT NULL LIMIT ?")
+                    .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Finalized)
+                    .bind::<BigInt, _>(limit)
+                    .load::<SolanaTransaction>(conn)
+            })
+            .await??;
 
-            match res {
-                Ok(Ok(transaction)) => Ok(transaction),
-                _ => Err(()),
-            }
-        } else {
-            println!("Failed to get connection to db from pool.");
-            Err(())
-        }
+        Ok(transactions)
+    }
+
+    /// Transactions the server broadcast itself that have not reached Confirmed
+    /// yet and can still land at `block_height`, least recently sent first.
+    pub async fn get_all_pending_rebroadcast(
+        pool: &deadpool_diesel::mysql::Pool,
+        block_height: u64,
+        limit: i64,
+    ) -> RepositoryResult<Vec<SolanaTransaction>> {
+        let conn = pool.get().await?;
+        let transactions = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT * FROM solana_transactions WHERE status < ? AND sent_at IS NOT NULL AND send_attempts > 0 AND last_valid_block_height >= ? ORDER BY COALESCE(last_resent_at, sent_at) LIMIT ?")
+                    .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Confirmed)
+                    .bind::<Unsigned<BigInt>, _>(block_height)
+                    .bind::<BigInt, _>(limit)
+                    .load::<SolanaTransaction>(conn)
+            })
+            .await??;
+
+        Ok(transactions)
+    }
+
+    /// Expires unsigned rows whose blockhash is no longer valid at
+    /// `block_height`. Rows with a Solana Pay reference are kept until
+    /// `references_created_before`, since the wallet may still have broadcast
+    /// them. Returns how many rows expired.
+    pub async fn expire_unsigned(
+        pool: &deadpool_diesel::mysql::Pool,
+        block_height: u64,
+        references_created_before: NaiveDateTime,
+        expired_at: NaiveDateTime,
+    ) -> RepositoryResult<usize> {
+        let conn = pool.get().await?;
+        let updated = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("UPDATE solana_transactions SET status = ?, updated_at = ? WHERE status = ? AND last_valid_block_height <= ? AND (reference IS NULL OR created_at < ?)")
+                    .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Expired)
+                    .bind::<Nullable<Datetime>, _>(expired_at)
+                    .bind::<Unsigned<SmallInt>, _>(TransactionStatus::Created)
+                    .bind::<Unsigned<BigInt>, _>(block_height)
+                    .bind::<Datetime, _>(references_created_before)
+                    .execute(conn)
+            })
+            .await??;
+
+        Ok(updated)
+    }
+
+    pub async fn record_rebroadcast(
+        pool: &deadpool_diesel::mysql::Pool,
+        id: i32,
+        resent_at: NaiveDateTime,
+    ) -> RepositoryResult<()> {
+        let conn = pool.get().await?;
+        conn.interact(move |conn: &mut MysqlConnection| {
+            diesel::sql_query("UPDATE solana_transactions SET send_attempts = send_attempts + 1, last_resent_at = ?, updated_at = ? WHERE id = ?")
+                .bind::<Nullable<Datetime>, _>(resent_at)
+                .bind::<Nullable<Datetime>, _>(resent_at)
+                .bind::<Integer, _>(id)
+                .execute(conn)
+        })
+        .await??;
 
+        Ok(())
     }
 
     pub async fn set_status_sent(
@@ -145,103 +497,279 @@ impl SolanaTransaction {
         signature: String,
         sent_at: NaiveDateTime,
         signed_tx: String,
-    ) -> Result<(), ()> {
+    ) -> RepositoryResult<()> {
         println!("TX ID TO UPDATE: {}", tx_id);
-        let conn = pool.get().await;
-        if let Ok(conn) = conn {
-            let now_utc: DateTime<Utc> = Utc::now();
-
-            let updated_at = NaiveDateTime::from_timestamp_opt(
-                now_utc.timestamp(),
-                now_utc.timestamp_subsec_millis() as u32 * 1_000_000,
-            );
-
-            let res = conn
-                .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("UPDATE solana_transactions SET status = ?, sent_at = ?, tx = ?, tx_signature = ?, updated_at = ? WHERE id = ?")
-                        .bind::<diesel::sql_types::Integer, _>(1) 
-                        .bind::<diesel::sql_types::Nullable<Datetime>, _>(sent_at) 
-                        .bind::<diesel::sql_types::Text, _>(&signed_tx)
-                        .bind::<diesel::sql_types::Nullable<Text>, _>(&signature)
-                        .bind::<diesel::sql_types::Nullable<Datetime>, _>(updated_at)
-                        .bind::<diesel::sql_types::Integer, _>(tx_id)
-                        .execute(conn)
+        let conn = pool.get().await?;
+        conn.interact(move |conn: &mut MysqlConnection| {
+            conn.transaction::<_, RepositoryError, _>(|conn| {
+                let tx = SolanaTransaction::load_for_update(conn, tx_id)?;
+                let transition = StatusTransition {
+                    tx: Some(signed_tx),
+                    tx_signature: Some(signature),
+                    send_attempts: Some(1),
+                    ..StatusTransition::new(&tx, TransactionStatus::Sent, sent_at)?
+                };
+                diesel::update(dsl::solana_transactions.find(tx_id))
+                    .set(&transition)
+                    .execute(conn)?;
+                Ok(())
+            })
+        })
+        .await??;
+
+        println!("Successfully updated solana_transaction in db");
+        Ok(())
+    }
+
+    /// Records a signature for a transaction that the wallet broadcast itself.
+    pub async fn set_status_sent_by_wallet(
+        pool: &deadpool_diesel::mysql::Pool,
+        tx_id: i32,
+        signature: String,
+        sent_at: NaiveDateTime,
+    ) -> RepositoryResult<()> {
+        let conn = pool.get().await?;
+        conn.interact(move |conn: &mut MysqlConnection| {
+            conn.transaction::<_, RepositoryError, _>(|conn| {
+                let tx = SolanaTransaction::load_for_update(conn, tx_id)?;
+                let transition = StatusTransition {
+                    tx_signature: Some(signature),
+                    ..StatusTransition::new(&tx, TransactionStatus::Sent, sent_at)?
+                };
+                let updated = diesel::update(
+                    dsl::solana_transactions
+                        .find(tx_id)
+                        .filter(dsl::status.eq(TransactionStatus::Created)),
+                )
+                .set(&transition)
+                .execute(conn)?;
+
+                if updated == 0 {
+                    return Err(RepositoryError::Conflict(
+                        "Transaction already submitted".to_string(),
+                    ));
+                }
+                Ok(())
+            })
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Writes a tracker cycle's status changes in a single database transaction.
+    /// Changes the row's current status no longer allows, e.g. because the
+    /// websocket tracker got there first, and rows deleted since they were
+    /// read are skipped.
+    pub async fn apply_status_changes(
+        pool: &deadpool_diesel::mysql::Pool,
+        changes: Vec<StatusChange>,
+    ) -> RepositoryResult<AppliedStatusChanges> {
+        let conn = pool.get().await?;
+        let applied = conn
+            .interact(move |conn: &mut MysqlConnection| {
+                conn.transaction::<_, RepositoryError, _>(|conn| {
+                    let ids: Vec<i32> = changes.iter().map(|change| change.id).collect();
+                    let txs = dsl::solana_transactions
+                        .filter(dsl::id.eq_any(ids))
+                        .select(SolanaTransaction::as_select())
+                        .for_update()
+                        .load::<SolanaTransaction>(conn)?;
+
+                    let mut applied = Vec::with_capacity(changes.len());
+                    for change in changes.into_iter() {
+                        let Some(tx) = txs.iter().find(|tx| tx.id == change.id) else {
+                            println!("Skipping status change for missing tx {}", change.id);
+                            continue;
+                        };
+                        let transition = match StatusTransition::new(tx, change.status, change.at) {
+                            Ok(transition) => transition
+                                .with_error(change.error.as_ref(), change.error_message.clone()),
+                            Err(RepositoryError::InvalidTransition { .. }) => continue,
+                            Err(err) => return Err(err),
+                        };
+                        diesel::update(dsl::solana_transactions.find(change.id))
+                            .set(&transition)
+                            .execute(conn)?;
+                        applied.push(change);
+                    }
+                    Ok(applied)
                 })
-                .await;
-
-            if let Ok(Ok(_)) = res {
-                println!("Successfully updated solana_transaction in db");
-                return Ok(());
-            } else {
-                println!("{:?}", res);
-                return Err(());
-            }
-        } else {
-            Err(())
-        }
+            })
+            .await??;
+
+        Ok(AppliedStatusChanges {
+            statements: applied.len() + 3,
+            changes: applied,
+        })
     }
 
     pub async fn set_status_confirmed(
         pool: &deadpool_diesel::mysql::Pool,
         id: i32,
-    ) -> Result<(), ()> {
+    ) -> RepositoryResult<()> {
         let confirmed_at = chrono::Utc::now().naive_utc();
-        SolanaTransaction::update_status(pool, id, 3, Some(confirmed_at)).await
+        SolanaTransaction::update_status(pool, id, TransactionStatus::Confirmed, confirmed_at).await
     }
 
     pub async fn set_status_finalized(
         pool: &deadpool_diesel::mysql::Pool,
         id: i32,
-    ) -> Result<(), ()> {
+    ) -> RepositoryResult<()> {
         let finalized_at = chrono::Utc::now().naive_utc();
-        SolanaTransaction::update_status(pool, id, 4, Some(finalized_at)).await
+        SolanaTransaction::update_status(pool, id, TransactionStatus::Finalized, finalized_at).await
     }
 
+    /// Marks a transaction that landed with an execution error as failed.
     pub async fn set_status_failed(
         pool: &deadpool_diesel::mysql::Pool,
         id: i32,
-    ) -> Result<(), ()> {
-        SolanaTransaction::update_status(pool, id, 5, None).await
+        error: TransactionError,
+        error_message: String,
+    ) -> RepositoryResult<()> {
+        let failed_at = chrono::Utc::now().naive_utc();
+        let conn = pool.get().await?;
+        conn.interact(move |conn: &mut MysqlConnection| {
+            conn.transaction::<_, RepositoryError, _>(|conn| {
+                let tx = SolanaTransaction::load_for_update(conn, id)?;
+                let transition = StatusTransition::new(&tx, TransactionStatus::Failed, failed_at)?
+                    .with_error(Some(&error), Some(error_message));
+                diesel::update(dsl::solana_transactions.find(id))
+                    .set(&transition)
+                    .execute(conn)?;
+                Ok(())
+            })
+        })
+        .await??;
+
+        Ok(())
     }
 
     async fn update_status(
         pool: &deadpool_diesel::mysql::Pool,
         id: i32,
-        status: u16,
-        datetime: Option<NaiveDateTime>,
-    ) -> Result<(), ()> {
-        let conn = pool.get().await;
-        if let Ok(conn) = conn {
-            let datetime_str = datetime
-                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
-                .unwrap_or_default();
-            let query = match status {
-                3 => format!(
-                    "UPDATE solana_transactions SET status = {}, confirmed_at = '{}' WHERE id = {}",
-                    status, datetime_str, id
-                ),
-                4 => format!(
-                    "UPDATE solana_transactions SET status = {}, finalized_at = '{}' WHERE id = {}",
-                    status, datetime_str, id
-                ),
-                5 => format!(
-                    "UPDATE solana_transactions SET status = {} WHERE id = {}",
-                    status, id
-                ),
-                _ => return Err(()), // Return an error if an unsupported status is provided
-            };
-
-            let res = conn
-                .interact(move |conn: &mut MysqlConnection| diesel::sql_query(query).execute(conn))
-                .await
-                .map_err(|_| ());
-
-            match res {
-                Ok(_) => Ok(()),
-                Err(_) => Err(()),
-            }
-        } else {
-            Err(())
+        status: TransactionStatus,
+        at: NaiveDateTime,
+    ) -> RepositoryResult<()> {
+        let conn = pool.get().await?;
+        conn.interact(move |conn: &mut MysqlConnection| {
+            conn.transaction::<_, RepositoryError, _>(|conn| {
+                let tx = SolanaTransaction::load_for_update(conn, id)?;
+                diesel::update(dsl::solana_transactions.find(id))
+                    .set(&StatusTransition::new(&tx, status, at)?)
+                    .execute(conn)?;
+                Ok(())
+            })
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    fn load_for_update(conn: &mut MysqlConnection, id: i32) -> RepositoryResult<SolanaTransaction> {
+        let tx = dsl::solana_transactions
+            .find(id)
+            .select(SolanaTransaction::as_select())
+            .for_update()
+            .get_result::<SolanaTransaction>(conn)?;
+        Ok(tx)
+    }
+}
+
+/// Columns written when a transaction moves to a new status. `None` fields
+/// are left untouched.
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = crate::schema::solana_transactions)]
+struct StatusTransition {
+    status: Option<TransactionStatus>,
+    updated_at: Option<NaiveDateTime>,
+    sent_at: Option<NaiveDateTime>,
+    confirmed_at: Option<NaiveDateTime>,
+    finalized_at: Option<NaiveDateTime>,
+    time_to_send: Option<u32>,
+    time_to_confirmed: Option<u32>,
+    time_to_finalized: Option<u32>,
+    tx: Option<String>,
+    tx_signature: Option<String>,
+    send_attempts: Option<u32>,
+    tx_error: Option<String>,
+    failed_instruction_index: Option<u8>,
+    tx_error_message: Option<String>,
+}
+
+impl StatusTransition {
+    /// Moves `tx` to `status` at `at`, filling in the matching timestamp and
+    /// its duration in milliseconds from the previous milestone. Fails if the
+    /// row's current status does not allow `status`.
+    fn new(
+        tx: &SolanaTransaction,
+        status: TransactionStatus,
+        at: NaiveDateTime,
+    ) -> RepositoryResult<Self> {
+        if !tx.status.can_transition_to(status) {
+            return Err(RepositoryError::InvalidTransition {
+                from: tx.status,
+                to: status,
+            });
+        }
+
+        let transition = StatusTransition {
+            status: Some(status),
+            updated_at: Some(at),
+            ..Default::default()
+        };
+        let transition = match status {
+            TransactionStatus::Sent => StatusTransition {
+                sent_at: Some(at),
+                time_to_send: millis_between(Some(tx.created_at), at),
+                ..transition
+            },
+            TransactionStatus::Confirmed => StatusTransition {
+                confirmed_at: Some(at),
+                time_to_confirmed: millis_between(tx.sent_at, at),
+                ..transition
+            },
+            // A row can skip Confirmed between two polls; it was confirmed by
+            // now, so it still counts towards the confirmation stats.
+            TransactionStatus::Finalized if tx.confirmed_at.is_none() => StatusTransition {
+                confirmed_at: Some(at),
+                time_to_confirmed: millis_between(tx.sent_at, at),
+                finalized_at: Some(at),
+                time_to_finalized: millis_between(tx.sent_at, at),
+                ..transition
+            },
+            TransactionStatus::Finalized => StatusTransition {
+                finalized_at: Some(at),
+                time_to_finalized: millis_between(tx.sent_at, at),
+                ..transition
+            },
+            TransactionStatus::Created
+            | TransactionStatus::Processed
+            | TransactionStatus::Failed
+            | TransactionStatus::Expired => transition,
+        };
+        Ok(transition)
+    }
+
+    /// Records the execution error of a transaction that failed on chain.
+    fn with_error(self, error: Option<&TransactionError>, message: Option<String>) -> Self {
+        let Some(error) = error else {
+            return self;
+        };
+        let failed_instruction_index = match error {
+            TransactionError::InstructionError(index, _) => Some(*index),
+            _ => None,
+        };
+        StatusTransition {
+            tx_error: serde_json::to_string(error).ok(),
+            failed_instruction_index,
+            tx_error_message: message,
+            ..self
         }
     }
 }
+
+fn millis_between(from: Option<NaiveDateTime>, to: NaiveDateTime) -> Option<u32> {
+    let millis = (to - from?).num_milliseconds();
+    u32::try_from(millis.max(0)).ok()
+}
@@ -0,0 +1,23 @@
+use tokio::sync::broadcast;
+
+use crate::transaction_status::TransactionStatus;
+
+/// Buffered events per subscriber before slow SSE clients start lagging.
+const STATUS_EVENTS_CAPACITY: usize = 1024;
+
+/// A status change written to the database by one of the trackers.
+#[derive(Debug, Clone)]
+pub struct TxStatusEvent {
+    pub tx_id: i32,
+    pub signature: Option<String>,
+    pub status: TransactionStatus,
+    /// Decoded execution error when `status` is Failed.
+    pub error: Option<String>,
+}
+
+pub type TxStatusSender = broadcast::Sender<TxStatusEvent>;
+
+pub fn channel() -> TxStatusSender {
+    let (sender, _) = broadcast::channel(STATUS_EVENTS_CAPACITY);
+    sender
+}
@@ -0,0 +1,290 @@
+use std::str::FromStr;
+
+use chrono::{Duration, Utc};
+use solana_client::{
+    rpc_config::RpcTransactionConfig, rpc_request::MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
+
+use crate::program_errors::{describe_transaction_error, transaction_logs};
+use crate::repository_error::{RepositoryError, RepositoryResult};
+use crate::solana_rpc::SolanaRpc;
+use crate::solana_transactions_repository::{SolanaTransaction, StatusChange};
+use crate::status_events::{TxStatusEvent, TxStatusSender};
+use crate::transaction_status::TransactionStatus;
+use crate::transaction_validation::{decode_transaction, validate_signed_transaction};
+
+/// Most sent transactions the tracker polls in a single cycle.
+pub const MAX_TRACKED_TRANSACTIONS: i64 = 4096;
+
+/// Most references looked up in a single cycle, one RPC call each.
+pub const MAX_TRACKED_REFERENCES: i64 = 32;
+
+/// Most transactions mentioning a reference fetched to find the one the
+/// server built, since anyone can attach the reference to another.
+const MAX_REFERENCE_CANDIDATES: usize = 4;
+
+/// How long after building a wallet-broadcast transaction the tracker looks
+/// for it. Its blockhash expires well before then.
+const REFERENCE_LOOKUP_WINDOW_SECS: i64 = 180;
+
+/// What one polling cycle did, for logging and benchmarks.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CycleStats {
+    /// Pending transactions read from the database.
+    pub tracked: usize,
+    /// Status changes written.
+    pub changed: usize,
+    pub rpc_calls: usize,
+    /// Statements sent to the database, including the transaction's BEGIN
+    /// and COMMIT.
+    pub db_round_trips: usize,
+}
+
+/// Polls the status of every pending transaction once and writes the
+/// changes in one database transaction, publishing each to `status_events`.
+pub async fn track_cycle(
+    pool: &deadpool_diesel::mysql::Pool,
+    rpc_client: &dyn SolanaRpc,
+    status_events: &TxStatusSender,
+) -> RepositoryResult<CycleStats> {
+    let mut stats = CycleStats::default();
+
+    let txs =
+        SolanaTransaction::get_all_not_finalized_or_failed(pool, MAX_TRACKED_TRANSACTIONS).await?;
+    stats.tracked = txs.len();
+    stats.db_round_trips += 1;
+    if txs.is_empty() {
+        return Ok(stats);
+    }
+
+    stats.rpc_calls += 1;
+    let Ok(epoch_data) = rpc_client.get_epoch_info().await else {
+        return Ok(stats);
+    };
+    let latest_block_height = epoch_data.block_height;
+    let now = Utc::now().naive_utc();
+    let mut changes = Vec::new();
+
+    // Past its last valid block height a transaction can no longer land, but
+    // it may have landed just before, so it only expires once its status
+    // comes back empty.
+    let expired = |tx: &SolanaTransaction| {
+        latest_block_height >= tx.last_valid_block_height
+            && tx.status.can_transition_to(TransactionStatus::Expired)
+    };
+    let expire = |tx: &SolanaTransaction| StatusChange {
+        id: tx.id,
+        status: TransactionStatus::Expired,
+        at: now,
+        error: None,
+        error_message: None,
+    };
+
+    let mut pending = Vec::new();
+    for tx in txs.iter() {
+        match tx
+            .tx_signature
+            .as_ref()
+            .and_then(|sig| Signature::from_str(sig).ok())
+        {
+            Some(sig) => pending.push((tx, sig)),
+            // Nothing to look up, so it cannot have landed.
+            None if expired(tx) => changes.push(expire(tx)),
+            None => {}
+        }
+    }
+
+    for chunk in pending.chunks(MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS) {
+        let sigs: Vec<Signature> = chunk.iter().map(|(_, sig)| *sig).collect();
+
+        stats.rpc_calls += 1;
+        let Ok(tx_status_response) = rpc_client.get_signature_statuses(&sigs).await else {
+            continue;
+        };
+        for ((tx, sig), status) in chunk.iter().zip(tx_status_response.value) {
+            let Some(status) = status else {
+                if expired(tx) {
+                    changes.push(expire(tx));
+                }
+                continue;
+            };
+            let next = match status.confirmation_status {
+                Some(TransactionConfirmationStatus::Processed) => TransactionStatus::Processed,
+                Some(TransactionConfirmationStatus::Confirmed) => TransactionStatus::Confirmed,
+                Some(TransactionConfirmationStatus::Finalized) => TransactionStatus::Finalized,
+                None => continue,
+            };
+            // An execution error is only final once the transaction can no
+            // longer be dropped from a fork.
+            let (next, error) = match status.err {
+                Some(err) if next != TransactionStatus::Processed => {
+                    (TransactionStatus::Failed, Some(err))
+                }
+                _ => (next, None),
+            };
+            if !tx.status.can_transition_to(next) {
+                continue;
+            }
+            let error_message = match error.as_ref() {
+                Some(err) => {
+                    stats.rpc_calls += 1;
+                    let logs = transaction_logs(rpc_client, sig).await;
+                    Some(describe_transaction_error(
+                        err,
+                        decode_transaction(&tx.tx).as_ref(),
+                        &logs,
+                    ))
+                }
+                None => None,
+            };
+            changes.push(StatusChange {
+                id: tx.id,
+                status: next,
+                at: now,
+                error,
+                error_message,
+            });
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok(stats);
+    }
+    let applied = SolanaTransaction::apply_status_changes(pool, changes).await?;
+    stats.db_round_trips += applied.statements;
+    stats.changed += applied.changes.len();
+    for change in applied.changes.into_iter() {
+        let signature = txs
+            .iter()
+            .find(|tx| tx.id == change.id)
+            .and_then(|tx| tx.tx_signature.clone());
+        let _ = status_events.send(TxStatusEvent {
+            tx_id: change.id,
+            signature,
+            status: change.status,
+            error: change.error_message,
+        });
+    }
+
+    Ok(stats)
+}
+
+/// Expires rows that were never signed once their blockhash is past
+/// `block_height`, so they do not build up as Created forever.
+pub async fn expire_unsigned(
+    pool: &deadpool_diesel::mysql::Pool,
+    block_height: u64,
+) -> RepositoryResult<usize> {
+    let now = Utc::now();
+    let references_created_before =
+        (now - Duration::seconds(REFERENCE_LOOKUP_WINDOW_SECS)).naive_utc();
+    SolanaTransaction::expire_unsigned(
+        pool,
+        block_height,
+        references_created_before,
+        now.naive_utc(),
+    )
+    .await
+}
+
+/// Marks Solana Pay and Actions transactions as Sent once the transaction the
+/// server built lands with their reference account, as the Solana Pay spec
+/// suggests, since the wallet broadcasts them and may not tell the server.
+/// Takes an RPC call per reference, so it runs less often than `track_cycle`.
+pub async fn resolve_references(
+    pool: &deadpool_diesel::mysql::Pool,
+    rpc_client: &dyn SolanaRpc,
+    status_events: &TxStatusSender,
+) -> RepositoryResult<CycleStats> {
+    let mut stats = CycleStats::default();
+    let created_after = (Utc::now() - Duration::seconds(REFERENCE_LOOKUP_WINDOW_SECS)).naive_utc();
+    let txs =
+        SolanaTransaction::get_all_pending_references(pool, created_after, MAX_TRACKED_REFERENCES)
+            .await?;
+    stats.db_round_trips += 1;
+
+    for tx in txs.iter() {
+        let Some(reference) = tx
+            .reference
+            .as_ref()
+            .and_then(|reference| Pubkey::from_str(reference).ok())
+        else {
+            continue;
+        };
+        stats.rpc_calls += 1;
+        let Ok(signatures) = rpc_client.get_signatures_for_address(&reference).await else {
+            continue;
+        };
+        // Newest first, so walk from the oldest. Only a transaction carrying
+        // the stored message is the wallet's; others may just mention the
+        // reference.
+        let mut found = None;
+        for candidate in signatures.iter().rev().take(MAX_REFERENCE_CANDIDATES) {
+            let Ok(signature) = Signature::from_str(&candidate.signature) else {
+                continue;
+            };
+            stats.rpc_calls += 1;
+            if landed_as_built(rpc_client, tx, &signature).await {
+                found = Some(candidate);
+                break;
+            }
+        }
+        let Some(found) = found else {
+            continue;
+        };
+
+        // BEGIN, SELECT ... FOR UPDATE, UPDATE and COMMIT.
+        stats.db_round_trips += 4;
+        let sent_at = Utc::now().naive_utc();
+        match SolanaTransaction::set_status_sent_by_wallet(
+            pool,
+            tx.id,
+            found.signature.clone(),
+            sent_at,
+        )
+        .await
+        {
+            Ok(()) => {}
+            Err(RepositoryError::Conflict(_) | RepositoryError::InvalidTransition { .. }) => {
+                continue
+            }
+            Err(err) => return Err(err),
+        }
+        stats.changed += 1;
+        let _ = status_events.send(TxStatusEvent {
+            tx_id: tx.id,
+            signature: Some(found.signature.clone()),
+            status: TransactionStatus::Sent,
+            error: None,
+        });
+    }
+
+    Ok(stats)
+}
+
+/// Whether `signature` landed as the transaction stored in `tx`, signed.
+async fn landed_as_built(
+    rpc_client: &dyn SolanaRpc,
+    tx: &SolanaTransaction,
+    signature: &Signature,
+) -> bool {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    let Ok(landed) = rpc_client
+        .get_transaction_with_config(signature, config)
+        .await
+    else {
+        return false;
+    };
+    landed
+        .transaction
+        .transaction
+        .decode()
+        .and_then(|landed_tx| landed_tx.into_legacy_transaction())
+        .is_some_and(|landed_tx| validate_signed_transaction(tx, landed_tx).is_ok())
+}
@@ -0,0 +1,220 @@
+use std::str::FromStr;
+
+use anchor_client::anchor_lang::InstructionData;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use t_vault::instruction;
+
+use crate::blockhash_cache::BlockhashCache;
+use crate::priority_fee::{estimate_compute_budget, PriorityFeeMode};
+use crate::repository_error::RepositoryError;
+use crate::solana_rpc::SolanaRpc;
+use crate::solana_transactions_repository::{NewSolanaTransaction, SolanaTransaction};
+use crate::transaction_status::TransactionStatus;
+
+/// The t-vault transactions the server knows how to build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxType {
+    Initialize,
+}
+
+impl FromStr for TxType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "initialize" => Ok(TxType::Initialize),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TxType {
+    pub const ALL: &'static [TxType] = &[TxType::Initialize];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TxType::Initialize => "initialize",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TxType::Initialize => "Initialize",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TxType::Initialize => "Initialize your t-vault.",
+        }
+    }
+
+    /// User supplied inputs for this transaction, used to build parameterized links.
+    pub fn parameters(&self) -> &'static [TxParameter] {
+        match self {
+            TxType::Initialize => &[],
+        }
+    }
+
+    pub fn button_id(&self) -> &'static str {
+        match self {
+            TxType::Initialize => "initialize-button",
+        }
+    }
+
+    /// Program this transaction's instructions call, not counting compute budget.
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            TxType::Initialize => Pubkey::from_str(&t_vault::id().to_string()).unwrap(),
+        }
+    }
+
+    /// Inputs `instructions` builds from, stored with the transaction so it
+    /// can be told apart without decoding it.
+    pub fn params(&self, _payer: &Pubkey) -> serde_json::Value {
+        match self {
+            TxType::Initialize => serde_json::json!({}),
+        }
+    }
+
+    pub fn instructions(&self, _payer: &Pubkey) -> Vec<Instruction> {
+        match self {
+            TxType::Initialize => {
+                let ix_data = instruction::Initialize {};
+
+                vec![Instruction::new_with_bytes(
+                    self.program_id(),
+                    &ix_data.data(),
+                    Vec::new(),
+                )]
+            }
+        }
+    }
+}
+
+/// Where a transaction was requested from, which decides how its signature
+/// reaches the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxSource {
+    /// The transaction modal, which submits the signed transaction back.
+    Modal,
+    /// A Solana Pay transaction request; the wallet broadcasts it and the
+    /// tracker finds it through its reference account.
+    SolanaPay,
+    /// A Solana Actions POST; the wallet broadcasts it and reports the
+    /// signature to the `next` callback, or the tracker finds it through its
+    /// reference account if the wallet never calls back.
+    Action,
+}
+
+impl TxSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TxSource::Modal => "modal",
+            TxSource::SolanaPay => "solana_pay",
+            TxSource::Action => "action",
+        }
+    }
+}
+
+pub struct TxParameter {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub required: bool,
+}
+
+#[derive(Debug)]
+pub enum BuildTransactionError {
+    Blockhash,
+    Serialize,
+    Database(RepositoryError),
+}
+
+pub struct BuiltTransaction {
+    pub tx_id: i32,
+    pub encoded_tx: String,
+}
+
+/// Builds an unsigned transaction for `payer` and stores it in `solana_transactions`.
+pub async fn build_transaction(
+    pool: &deadpool_diesel::mysql::Pool,
+    rpc_client: &dyn SolanaRpc,
+    blockhash_cache: &BlockhashCache,
+    tx_type: TxType,
+    payer: &Pubkey,
+    priority_fee_mode: PriorityFeeMode,
+    source: TxSource,
+) -> Result<BuiltTransaction, BuildTransactionError> {
+    let mut ixs = tx_type.instructions(payer);
+    // Transactions the wallet broadcasts get a fresh account, unique to this
+    // request, to look them up by on chain.
+    let reference = match source {
+        TxSource::SolanaPay | TxSource::Action => Some(Keypair::new().pubkey()),
+        TxSource::Modal => None,
+    };
+    if let (Some(reference), Some(ix)) = (reference, ixs.last_mut()) {
+        ix.accounts
+            .push(AccountMeta::new_readonly(reference, false));
+    }
+    println!("Created ix...");
+
+    let (blockhash, last_valid_block_height) = blockhash_cache
+        .get()
+        .await
+        .map_err(|_| BuildTransactionError::Blockhash)?;
+    println!("Got latest blockhash");
+
+    let compute_budget =
+        estimate_compute_budget(rpc_client, priority_fee_mode, &ixs, payer, &blockhash).await;
+    let ixs = compute_budget.prepend_to(&ixs);
+
+    let message = Message::new_with_blockhash(&ixs, Some(payer), &blockhash);
+    // Shown in the history; a build does not fail over a missing quote.
+    let fee = rpc_client.get_fee_for_message(&message).await.ok();
+
+    let tx = Transaction::new_unsigned(message);
+    let serialized_tx = bincode::serialize(&tx).map_err(|_| BuildTransactionError::Serialize)?;
+
+    let encoded_tx = BASE64.encode(serialized_tx);
+
+    let now_utc: DateTime<Utc> = Utc::now();
+
+    let now_naive_with_ms = NaiveDateTime::from_timestamp_opt(
+        now_utc.timestamp(),
+        now_utc.timestamp_subsec_millis() * 1_000_000,
+    )
+    .expect("To get valid NaiveDateTime");
+    let new_db_tx = NewSolanaTransaction {
+        blockhash: blockhash.to_string(),
+        last_valid_block_height,
+        status: TransactionStatus::Created,
+        tx: encoded_tx.clone(),
+        created_at: now_naive_with_ms,
+        sent_at: None,
+        priority_fee: compute_budget
+            .compute_unit_price
+            .map(|micro_lamports| micro_lamports.min(u32::MAX as u64) as u32),
+        owner: payer.to_string(),
+        tx_type: tx_type.as_str().to_string(),
+        program_id: tx_type.program_id().to_string(),
+        params: Some(tx_type.params(payer)),
+        source: source.as_str().to_string(),
+        reference: reference.map(|reference| reference.to_string()),
+        fee,
+    };
+
+    let tx_id = SolanaTransaction::insert(pool, new_db_tx)
+        .await
+        .map_err(BuildTransactionError::Database)?;
+
+    Ok(BuiltTransaction { tx_id, encoded_tx })
+}
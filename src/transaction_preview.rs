@@ -0,0 +1,56 @@
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::{commitment_config::CommitmentConfig, transaction::Transaction};
+
+use crate::program_errors::describe_transaction_error;
+use crate::solana_rpc::SolanaRpc;
+
+/// What an unsigned transaction is expected to do, shown before the wallet prompt.
+#[derive(Debug, Default)]
+pub struct TxPreview {
+    /// False when the RPC node could not simulate; signing is still allowed.
+    pub simulated: bool,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+    /// Expected fee in lamports.
+    pub fee: Option<u64>,
+    /// Why the simulation failed. Signing is blocked while this is set.
+    pub error: Option<String>,
+}
+
+/// Simulates `tx` without signatures against the latest blockhash.
+pub async fn preview_transaction(rpc_client: &dyn SolanaRpc, tx: &Transaction) -> TxPreview {
+    let simulate_config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::processed()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let fee = rpc_client.get_fee_for_message(&tx.message).await.ok();
+
+    let simulation = match rpc_client
+        .simulate_transaction_with_config(tx, simulate_config)
+        .await
+    {
+        Ok(response) => response.value,
+        Err(err) => {
+            println!("Failed to simulate transaction: {}", err);
+            return TxPreview {
+                fee,
+                ..TxPreview::default()
+            };
+        }
+    };
+
+    let logs = simulation.logs.unwrap_or_default();
+    let error = simulation
+        .err
+        .map(|err| describe_transaction_error(&err, Some(tx), &logs));
+
+    TxPreview {
+        simulated: true,
+        units_consumed: simulation.units_consumed,
+        logs,
+        fee,
+        error,
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::str::FromStr;
+
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::mysql::{Mysql, MysqlValue};
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::{SmallInt, Unsigned};
+use serde::{Deserialize, Serialize};
+
+/// Lifecycle of a row in `solana_transactions`, stored in the `status` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Unsigned<SmallInt>)]
+pub enum TransactionStatus {
+    /// Built and stored, waiting for a signature.
+    Created = 0,
+    /// Broadcast, not yet seen by the cluster.
+    Sent = 1,
+    Processed = 2,
+    Confirmed = 3,
+    Finalized = 4,
+    /// Rejected by the cluster.
+    Failed = 5,
+    /// Blockhash expired before the transaction landed.
+    Expired = 6,
+}
+
+impl TransactionStatus {
+    pub const ALL: &'static [TransactionStatus] = &[
+        TransactionStatus::Created,
+        TransactionStatus::Sent,
+        TransactionStatus::Processed,
+        TransactionStatus::Confirmed,
+        TransactionStatus::Finalized,
+        TransactionStatus::Failed,
+        TransactionStatus::Expired,
+    ];
+
+    pub fn from_u16(status: u16) -> Option<Self> {
+        match status {
+            0 => Some(TransactionStatus::Created),
+            1 => Some(TransactionStatus::Sent),
+            2 => Some(TransactionStatus::Processed),
+            3 => Some(TransactionStatus::Confirmed),
+            4 => Some(TransactionStatus::Finalized),
+            5 => Some(TransactionStatus::Failed),
+            6 => Some(TransactionStatus::Expired),
+            _ => None,
+        }
+    }
+
+    pub fn as_u16(self) -> u16 {
+        self as u16
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransactionStatus::Created => "Created",
+            TransactionStatus::Sent => "Sent",
+            TransactionStatus::Processed => "Processed",
+            TransactionStatus::Confirmed => "Confirmed",
+            TransactionStatus::Finalized => "Finalized",
+            TransactionStatus::Failed => "Failed",
+            TransactionStatus::Expired => "Expired",
+        }
+    }
+
+    /// Whether a row in this status can move to `next`. Commitment levels only
+    /// move forward and may skip steps; Finalized, Failed and Expired are final.
+    /// A transaction expires when it was never signed, never landed, or was
+    /// only processed on a fork that was dropped.
+    pub fn can_transition_to(self, next: TransactionStatus) -> bool {
+        use TransactionStatus::*;
+
+        matches!(
+            (self, next),
+            (Created, Sent | Expired)
+                | (Sent, Processed | Confirmed | Finalized | Failed | Expired)
+                | (Processed, Confirmed | Finalized | Failed | Expired)
+                | (Confirmed, Finalized)
+        )
+    }
+
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            TransactionStatus::Finalized | TransactionStatus::Failed | TransactionStatus::Expired
+        )
+    }
+}
+
+impl FromStr for TransactionStatus {
+    type Err = ();
+
+    /// Parses the name returned by [`TransactionStatus::as_str`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Created" => Ok(TransactionStatus::Created),
+            "Sent" => Ok(TransactionStatus::Sent),
+            "Processed" => Ok(TransactionStatus::Processed),
+            "Confirmed" => Ok(TransactionStatus::Confirmed),
+            "Finalized" => Ok(TransactionStatus::Finalized),
+            "Failed" => Ok(TransactionStatus::Failed),
+            "Expired" => Ok(TransactionStatus::Expired),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for TransactionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl ToSql<Unsigned<SmallInt>, Mysql> for TransactionStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Mysql>) -> serialize::Result {
+        ToSql::<Unsigned<SmallInt>, Mysql>::to_sql(&self.as_u16(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Unsigned<SmallInt>, Mysql> for TransactionStatus {
+    fn from_sql(bytes: MysqlValue<'_>) -> deserialize::Result<Self> {
+        let status: u16 = FromSql::<Unsigned<SmallInt>, Mysql>::from_sql(bytes)?;
+        TransactionStatus::from_u16(status)
+            .ok_or_else(|| format!("Unknown transaction status {}", status).into())
+    }
+}
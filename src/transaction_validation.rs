@@ -0,0 +1,138 @@
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::Transaction};
+
+use crate::solana_transactions_repository::SolanaTransaction;
+use crate::transaction_status::TransactionStatus;
+
+/// Reasons a submitted signed transaction is rejected for a stored `tx_id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxValidationError {
+    InvalidEncoding,
+    InvalidStoredTransaction,
+    AlreadySubmitted,
+    BlockhashMismatch,
+    FeePayerMismatch,
+    InstructionMismatch,
+    MessageMismatch,
+    MissingSignature,
+    InvalidSignature,
+}
+
+impl fmt::Display for TxValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            TxValidationError::InvalidEncoding => "Submitted transaction could not be decoded",
+            TxValidationError::InvalidStoredTransaction => "Stored transaction could not be decoded",
+            TxValidationError::AlreadySubmitted => "Transaction was already submitted",
+            TxValidationError::BlockhashMismatch => "Blockhash does not match the built transaction",
+            TxValidationError::FeePayerMismatch => "Fee payer does not match the built transaction",
+            TxValidationError::InstructionMismatch => {
+                "Instructions do not match the built transaction"
+            }
+            TxValidationError::MessageMismatch => "Message does not match the built transaction",
+            TxValidationError::MissingSignature => "Transaction is missing a required signature",
+            TxValidationError::InvalidSignature => "Transaction signature verification failed",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+pub fn decode_transaction(encoded_tx: &str) -> Option<Transaction> {
+    let serialized_tx = BASE64.decode(encoded_tx).ok()?;
+    bincode::deserialize(&serialized_tx).ok()
+}
+
+/// Whether `encoded_signed_tx` carries a valid fee payer signature from `owner`.
+pub fn signed_by_fee_payer(encoded_signed_tx: &str, owner: &str) -> bool {
+    let Some(signed_tx) = decode_transaction(encoded_signed_tx) else {
+        return false;
+    };
+    let (Some(fee_payer), Some(signature)) = (
+        signed_tx.message.account_keys.first(),
+        signed_tx.signatures.first(),
+    ) else {
+        return false;
+    };
+    fee_payer.to_string() == owner
+        && signature.verify(fee_payer.as_ref(), &signed_tx.message_data())
+}
+
+/// Checks that `encoded_signed_tx` is the stored unsigned transaction, fully signed.
+pub fn validate_submitted_transaction(
+    db_tx: &SolanaTransaction,
+    encoded_signed_tx: &str,
+) -> Result<Transaction, TxValidationError> {
+    let signed_tx =
+        decode_transaction(encoded_signed_tx).ok_or(TxValidationError::InvalidEncoding)?;
+    validate_signed_transaction(db_tx, signed_tx)
+}
+
+/// Checks that `signed_tx` is the stored unsigned transaction, fully signed.
+pub fn validate_signed_transaction(
+    db_tx: &SolanaTransaction,
+    signed_tx: Transaction,
+) -> Result<Transaction, TxValidationError> {
+    if db_tx.status != TransactionStatus::Created || db_tx.tx_signature.is_some() {
+        return Err(TxValidationError::AlreadySubmitted);
+    }
+
+    let stored_tx =
+        decode_transaction(&db_tx.tx).ok_or(TxValidationError::InvalidStoredTransaction)?;
+
+    if signed_tx.message.recent_blockhash != stored_tx.message.recent_blockhash {
+        return Err(TxValidationError::BlockhashMismatch);
+    }
+
+    if signed_tx.message.account_keys.first() != stored_tx.message.account_keys.first() {
+        return Err(TxValidationError::FeePayerMismatch);
+    }
+
+    if resolved_instructions(&signed_tx) != resolved_instructions(&stored_tx) {
+        return Err(TxValidationError::InstructionMismatch);
+    }
+
+    if signed_tx.message_data() != stored_tx.message_data() {
+        return Err(TxValidationError::MessageMismatch);
+    }
+
+    let required_signatures = signed_tx.message.header.num_required_signatures as usize;
+    if signed_tx.signatures.len() != required_signatures
+        || signed_tx
+            .signatures
+            .iter()
+            .any(|signature| *signature == Signature::default())
+    {
+        return Err(TxValidationError::MissingSignature);
+    }
+
+    if !signed_tx.verify_with_results().iter().all(|valid| *valid) {
+        return Err(TxValidationError::InvalidSignature);
+    }
+
+    Ok(signed_tx)
+}
+
+/// An instruction as (program id, account keys, data).
+type ResolvedInstruction = (Pubkey, Vec<Pubkey>, Vec<u8>);
+
+/// Resolves instructions against the account table so that differently ordered
+/// account tables still compare by what they actually execute.
+fn resolved_instructions(tx: &Transaction) -> Option<Vec<ResolvedInstruction>> {
+    let account_keys = &tx.message.account_keys;
+    tx.message
+        .instructions
+        .iter()
+        .map(|ix| {
+            let program_id = *account_keys.get(ix.program_id_index as usize)?;
+            let accounts = ix
+                .accounts
+                .iter()
+                .map(|index| account_keys.get(*index as usize).copied())
+                .collect::<Option<Vec<Pubkey>>>()?;
+            Some((program_id, accounts, ix.data.clone()))
+        })
+        .collect()
+}
@@ -21,13 +21,29 @@ function encodeTx(byteArray) {
   return encodedTx;
 }
 
-// used by hx-vals to add the pubkey to requests
-function getPubkey() {
-  const wallet = window.solflare;
-  if (wallet && wallet.isConnected) {
-    return wallet.publicKey.toString();
+// Sign-In With Solana: sign the server's challenge so modal and submit
+// requests are tied to this wallet through the session cookie
+async function signIn(wallet) {
+  const pubkey = wallet.publicKey.toString();
+  const challenge = await fetch(`/auth/challenge?pubkey=${pubkey}`);
+  if (!challenge.ok) {
+    throw new Error('Failed to get sign-in challenge');
+  }
+  const { message } = await challenge.json();
+
+  const signed = await wallet.signMessage(new TextEncoder().encode(message), "utf8");
+  const signature = signed.signature || signed;
+
+  const response = await fetch('/auth/verify', {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    body: JSON.stringify({ pubkey, signature: encodeTx(signature) }),
+  });
+  if (!response.ok) {
+    throw new Error('Sign-in was rejected');
   }
-  return "";
 }
 
 // used by tx-modal signAndSend button
@@ -61,6 +77,14 @@ async function signAndSend(element) {
 
 }
 
+// htmx drops 4xx responses by default; show rejected submissions in the modal.
+document.addEventListener('htmx:beforeSwap', function(evt) {
+  if (evt.detail.xhr.status === 400 || evt.detail.xhr.status === 409) {
+    evt.detail.shouldSwap = true;
+    evt.detail.isError = false;
+  }
+});
+
 document.addEventListener('DOMContentLoaded', () => {
   const wallet = window.solflare;
 
@@ -125,6 +149,8 @@ document.addEventListener('DOMContentLoaded', () => {
     try {
       await wallet.connect({ onlyIfTrusted: true });
       console.log("Connected to wallet");
+      await signIn(wallet);
+      console.log("Signed in");
       updateUI();
     } catch (err) {
       console.error("Failed to connect to the wallet:", err);
@@ -134,6 +160,7 @@ document.addEventListener('DOMContentLoaded', () => {
   async function disconnectWallet() {
     try {
       await wallet.disconnect();
+      await fetch('/auth/sign-out', { method: 'POST' });
       console.log("Disconnected from wallet");
       updateUI();
     } catch (err) {
@@ -0,0 +1,101 @@
+//! Inserts racing on a shared pool must each get back their own row's id.
+//! Needs a migrated database:
+//!
+//!     DATABASE_URL=mysql://... cargo test --test concurrent_inserts -- --ignored
+
+use std::collections::HashSet;
+
+use deadpool_diesel::mysql::{Manager, Pool};
+use diesel::prelude::*;
+use solana_sdk::pubkey::Pubkey;
+use t_vault_web_server::repository::{self, NewUserDb};
+use t_vault_web_server::schema::{solana_transactions, users};
+use t_vault_web_server::solana_transactions_repository::{NewSolanaTransaction, SolanaTransaction};
+use tokio::task::JoinSet;
+
+const PARALLEL_INSERTS: usize = 500;
+
+fn pool() -> Pool {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL not set in env.");
+    Pool::builder(Manager::new(database_url, deadpool_diesel::Runtime::Tokio1))
+        .build()
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+#[ignore = "needs DATABASE_URL"]
+async fn concurrent_transaction_inserts_return_their_own_ids() {
+    let pool = pool();
+    let owner = Pubkey::new_unique().to_string();
+
+    let mut inserts = JoinSet::new();
+    for _ in 0..PARALLEL_INSERTS {
+        let pool = pool.clone();
+        let owner = owner.clone();
+        inserts.spawn(async move {
+            // The blockhash tells the rows apart.
+            let new_tx = NewSolanaTransaction::fixture(owner, 0);
+            let blockhash = new_tx.blockhash.clone();
+            let id = SolanaTransaction::insert(&pool, new_tx).await.unwrap();
+            (id, blockhash)
+        });
+    }
+
+    let mut ids = HashSet::new();
+    while let Some(result) = inserts.join_next().await {
+        let (id, blockhash) = result.unwrap();
+        assert!(ids.insert(id), "id {} returned twice", id);
+        let stored = SolanaTransaction::get_by_id(&pool, id).await.unwrap();
+        assert_eq!(stored.blockhash, blockhash);
+    }
+    assert_eq!(ids.len(), PARALLEL_INSERTS);
+
+    let conn = pool.get().await.unwrap();
+    conn.interact(move |conn| {
+        diesel::delete(solana_transactions::table.filter(solana_transactions::owner.eq(owner)))
+            .execute(conn)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+#[ignore = "needs DATABASE_URL"]
+async fn concurrent_user_inserts_return_their_own_ids() {
+    let pool = pool();
+
+    let mut inserts = JoinSet::new();
+    for _ in 0..PARALLEL_INSERTS {
+        let pool = pool.clone();
+        inserts.spawn(async move {
+            let pubkey = Pubkey::new_unique().to_string();
+            let new_user = NewUserDb {
+                name: "concurrent insert".to_string(),
+                age: None,
+                pubkey: Some(pubkey.clone()),
+            };
+            let user = repository::insert(&pool, new_user).await.unwrap();
+            (user.id, pubkey)
+        });
+    }
+
+    let mut ids = HashSet::new();
+    let mut pubkeys = Vec::new();
+    while let Some(result) = inserts.join_next().await {
+        let (id, pubkey) = result.unwrap();
+        assert!(ids.insert(id), "id {} returned twice", id);
+        let stored = repository::get(&pool, id).await.unwrap();
+        assert_eq!(stored.pubkey.as_deref(), Some(pubkey.as_str()));
+        pubkeys.push(pubkey);
+    }
+    assert_eq!(ids.len(), PARALLEL_INSERTS);
+
+    let conn = pool.get().await.unwrap();
+    conn.interact(move |conn| {
+        diesel::delete(users::table.filter(users::pubkey.eq_any(pubkeys))).execute(conn)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+}
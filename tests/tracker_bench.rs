@@ -0,0 +1,84 @@
+//! Cost of one polling cycle with thousands of pending transactions. Needs a
+//! migrated database:
+//!
+//!     DATABASE_URL=mysql://... cargo test --test tracker_bench -- --ignored --nocapture
+
+use chrono::Utc;
+use deadpool_diesel::mysql::{Manager, Pool};
+use diesel::prelude::*;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::{
+    message::Message, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+};
+use t_vault_web_server::schema::solana_transactions::dsl;
+use t_vault_web_server::solana_rpc::{MockRpc, SolanaRpc};
+use t_vault_web_server::solana_transactions_repository::{NewSolanaTransaction, SolanaTransaction};
+use t_vault_web_server::status_events;
+use t_vault_web_server::tracker::{track_cycle, MAX_TRACKED_TRANSACTIONS};
+
+const PENDING_ROWS: usize = 4000;
+
+/// One in this many sent transactions never lands.
+const DROPPED_EVERY: usize = 4;
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn tracker_cycle_cost() {
+    assert!(PENDING_ROWS as i64 <= MAX_TRACKED_TRANSACTIONS);
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL not set in env.");
+    let pool = Pool::builder(Manager::new(database_url, deadpool_diesel::Runtime::Tokio1))
+        .build()
+        .unwrap();
+    let rpc = MockRpc::new();
+    let status_events = status_events::channel();
+    // Rows are tagged with a fresh owner so they can be removed afterwards.
+    let owner = Pubkey::new_unique().to_string();
+
+    let send_config = RpcSendTransactionConfig {
+        skip_preflight: true,
+        ..RpcSendTransactionConfig::default()
+    };
+    for i in 0..PENDING_ROWS {
+        let now = Utc::now().naive_utc();
+        let new_tx = NewSolanaTransaction::fixture(owner.clone(), rpc.block_height() + 150);
+        let id = SolanaTransaction::insert(&pool, new_tx).await.unwrap();
+
+        let tx = Transaction {
+            signatures: vec![Signature::new_unique()],
+            message: Message::default(),
+        };
+        SolanaTransaction::set_status_sent(
+            &pool,
+            id,
+            tx.signatures[0].to_string(),
+            now,
+            String::new(),
+        )
+        .await
+        .unwrap();
+        if i % DROPPED_EVERY != 0 {
+            rpc.send_transaction_with_config(&tx, send_config)
+                .await
+                .unwrap();
+        }
+    }
+
+    for (label, blocks) in [("processed", 0), ("confirmed", 1), ("finalized", 32)] {
+        rpc.advance_block_height(blocks);
+        let calls_before = rpc.calls();
+        let stats = track_cycle(&pool, &rpc, &status_events).await.unwrap();
+        println!(
+            "{}: tracked {} txs, {} changed, {} rpc calls, {} db round trips",
+            label, stats.tracked, stats.changed, stats.rpc_calls, stats.db_round_trips
+        );
+        assert_eq!(stats.rpc_calls, rpc.calls() - calls_before);
+    }
+
+    let conn = pool.get().await.unwrap();
+    conn.interact(move |conn| {
+        diesel::delete(dsl::solana_transactions.filter(dsl::owner.eq(owner))).execute(conn)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+}
@@ -0,0 +1,453 @@
+//! Drives a transaction through the router from sign-in to finalization
+//! against `MockRpc`. Needs a migrated database:
+//!
+//!     DATABASE_URL=mysql://... cargo test --test tx_flow -- --ignored
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use deadpool_diesel::mysql::{Manager, Pool};
+use diesel::prelude::*;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::TransactionError;
+use t_vault_web_server::app::{app, AppState};
+use t_vault_web_server::blockhash_cache::BlockhashCache;
+use t_vault_web_server::config::Config;
+use t_vault_web_server::priority_fee::PriorityFeeMode;
+use t_vault_web_server::rpc_pool::{RpcEndpointConfig, RpcPool, RpcRole};
+use t_vault_web_server::schema::{solana_transactions, users};
+use t_vault_web_server::session::SessionConfig;
+use t_vault_web_server::signature_subscriber::{SignatureSubscriber, TrackingMode};
+use t_vault_web_server::solana_rpc::{MockRpc, SolanaRpc};
+use t_vault_web_server::status_events::{self, TxStatusSender};
+use t_vault_web_server::tracker::track_cycle;
+use t_vault_web_server::transaction_validation::decode_transaction;
+use tokio::sync::Mutex;
+use tower::ServiceExt;
+
+const PUBLIC_URL: &str = "http://localhost:3000";
+
+/// Each server's tracker polls every pending row in the shared database
+/// against its own `MockRpc`, so the tests must not run at once.
+static DATABASE: Mutex<()> = Mutex::const_new(());
+
+struct TestServer {
+    router: Router,
+    pool: Arc<Pool>,
+    rpc: Arc<MockRpc>,
+    status_events: TxStatusSender,
+}
+
+fn server() -> TestServer {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL not set in env.");
+    let pool = Arc::new(
+        Pool::builder(Manager::new(
+            database_url.clone(),
+            deadpool_diesel::Runtime::Tokio1,
+        ))
+        .build()
+        .unwrap(),
+    );
+    let rpc = Arc::new(MockRpc::new());
+    let rpc_client: Arc<dyn SolanaRpc> = rpc.clone();
+    let status_events = status_events::channel();
+    let config = Config {
+        rpc_endpoints: vec![RpcEndpointConfig {
+            url: "http://mock".to_string(),
+            role: RpcRole::All,
+            weight: 1,
+        }],
+        rpc_send_fanout: false,
+        session_secret: None,
+        rpc_stats_token: None,
+        database_url,
+        public_url: PUBLIC_URL.to_string(),
+        actions_blockchain_id: String::new(),
+        rebroadcast_interval: Duration::from_secs(2),
+        rpc_timeout: Duration::from_secs(1),
+        blockhash_refresh_interval: Duration::from_millis(400),
+        blockhash_max_age: Duration::from_secs(2),
+        priority_fee_mode: PriorityFeeMode::Off,
+        tracking_mode: TrackingMode::Polling,
+        ws_url: String::new(),
+        explorer_tx_url: String::new(),
+    };
+
+    let router = app(AppState {
+        database_pool: pool.clone(),
+        rpc_client: rpc_client.clone(),
+        rpc_pool: Arc::new(RpcPool::new(Vec::new(), false)),
+        // Refreshed in the background, so it gets its own cluster rather
+        // than spending the failures a test scripts.
+        blockhash_cache: BlockhashCache::spawn(
+            Arc::new(MockRpc::new()),
+            config.blockhash_refresh_interval,
+            config.blockhash_max_age,
+        ),
+        session_config: SessionConfig::new(None, PUBLIC_URL),
+        signature_subscriber: SignatureSubscriber::disabled(),
+        status_events: status_events.clone(),
+        config: Arc::new(config),
+    });
+    TestServer {
+        router,
+        pool,
+        rpc,
+        status_events,
+    }
+}
+
+/// Sends `request` and returns the status, the cookies set and the body.
+async fn send(router: &Router, request: Request<Body>) -> (StatusCode, Vec<String>, String) {
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let cookies = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .map(str::to_string)
+        .collect();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, cookies, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Value of the first `name="..."` attribute in `html`. Base64 and ids need
+/// no unescaping.
+fn attribute(html: &str, name: &str) -> String {
+    let start = html.find(&format!("{}=\"", name)).unwrap() + name.len() + 2;
+    let end = start + html[start..].find('"').unwrap();
+    html[start..end].to_string()
+}
+
+/// Percent-encodes the characters base64 uses that a form body does not allow.
+fn form_value(value: &str) -> String {
+    value
+        .replace('+', "%2B")
+        .replace('/', "%2F")
+        .replace('=', "%3D")
+}
+
+async fn sign_in(router: &Router, keypair: &Keypair) -> String {
+    let (status, cookies, body) = send(
+        router,
+        Request::get(format!("/auth/challenge?pubkey={}", keypair.pubkey()))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let message = serde_json::from_str::<serde_json::Value>(&body).unwrap()["message"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let signature = BASE64.encode(keypair.sign_message(message.as_bytes()).as_ref());
+
+    let (status, cookies, _) = send(
+        router,
+        Request::post("/auth/verify")
+            .header(header::COOKIE, cookies.join("; "))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({
+                    "pubkey": keypair.pubkey().to_string(),
+                    "signature": signature,
+                })
+                .to_string(),
+            ))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    cookies
+        .into_iter()
+        .find(|cookie| cookie.starts_with("session="))
+        .expect("no session cookie")
+}
+
+/// Opens the modal for an initialize transaction and signs what it built,
+/// returning the tx id, the signed transaction and its signature.
+async fn create_and_sign(
+    router: &Router,
+    session: &str,
+    keypair: &Keypair,
+) -> (String, String, String) {
+    let (status, _, modal) = send(
+        router,
+        Request::get("/tx-modal?tx_type=initialize")
+            .header(header::COOKIE, session)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", modal);
+    let tx_id = attribute(&modal, "tx-id");
+    let mut tx = decode_transaction(&attribute(&modal, "encoded-tx")).unwrap();
+    assert_eq!(tx.message.account_keys[0], keypair.pubkey());
+
+    let blockhash = tx.message.recent_blockhash;
+    tx.sign(&[keypair], blockhash);
+    let signature = tx.signatures[0].to_string();
+    let encoded_tx = BASE64.encode(bincode::serialize(&tx).unwrap());
+    (tx_id, encoded_tx, signature)
+}
+
+async fn submit(
+    router: &Router,
+    session: &str,
+    tx_id: &str,
+    encoded_tx: &str,
+) -> (StatusCode, String) {
+    let (status, _, body) = send(
+        router,
+        Request::post("/tx-submit")
+            .header(header::COOKIE, session)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(format!(
+                "txId={}&encodedSerializedTx={}",
+                tx_id,
+                form_value(encoded_tx)
+            )))
+            .unwrap(),
+    )
+    .await;
+    (status, body)
+}
+
+async fn track(server: &TestServer) {
+    track_cycle(&server.pool, server.rpc.as_ref(), &server.status_events)
+        .await
+        .unwrap();
+}
+
+async fn cleanup(pool: &Pool, owner: String) {
+    let conn = pool.get().await.unwrap();
+    conn.interact(move |conn| {
+        diesel::delete(solana_transactions::table.filter(solana_transactions::owner.eq(&owner)))
+            .execute(conn)?;
+        diesel::delete(users::table.filter(users::pubkey.eq(&owner))).execute(conn)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+}
+
+async fn status_data(router: &Router, session: &str, signature: &str) -> serde_json::Value {
+    let (status, _, body) = send(
+        router,
+        Request::get(format!("/tx-status-data?tx_signature={}", signature))
+            .header(header::COOKIE, session)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn transaction_is_created_signed_submitted_and_finalized() {
+    let _database = DATABASE.lock().await;
+    let server = server();
+    let keypair = Keypair::new();
+    let session = sign_in(&server.router, &keypair).await;
+
+    let (tx_id, encoded_tx, signature) = create_and_sign(&server.router, &session, &keypair).await;
+    let (status, body) = submit(&server.router, &session, &tx_id, &encoded_tx).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        status_data(&server.router, &session, &signature).await["status"],
+        "Sent"
+    );
+
+    // The mock lands sent transactions right away, confirms them a block
+    // later and finalizes them 32 blocks after that.
+    for (blocks, expected) in [(0, "Processed"), (1, "Confirmed"), (32, "Finalized")] {
+        server.rpc.advance_block_height(blocks);
+        track(&server).await;
+        let data = status_data(&server.router, &session, &signature).await;
+        assert_eq!(data["status"], expected);
+        assert_eq!(data["tx_id"].to_string(), tx_id);
+        assert!(data["error"].is_null());
+    }
+
+    // The status stream needs no session and ends once the status is final.
+    let (status, _, stream) = send(
+        &server.router,
+        Request::get(format!("/tx-status-stream?tx_signature={}", signature))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(stream.contains("data: Finalized"), "{}", stream);
+
+    cleanup(&server.pool, keypair.pubkey().to_string()).await;
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn dropped_transaction_expires_with_its_blockhash() {
+    let _database = DATABASE.lock().await;
+    let server = server();
+    let keypair = Keypair::new();
+    let session = sign_in(&server.router, &keypair).await;
+
+    server.rpc.set_drop_transactions(true);
+    let (tx_id, encoded_tx, signature) = create_and_sign(&server.router, &session, &keypair).await;
+    let (status, body) = submit(&server.router, &session, &tx_id, &encoded_tx).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // Still valid, so the tracker keeps waiting for it to land.
+    track(&server).await;
+    assert_eq!(
+        status_data(&server.router, &session, &signature).await["status"],
+        "Sent"
+    );
+
+    server.rpc.advance_block_height(151);
+    track(&server).await;
+    assert_eq!(
+        status_data(&server.router, &session, &signature).await["status"],
+        "Expired"
+    );
+
+    cleanup(&server.pool, keypair.pubkey().to_string()).await;
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn rpc_errors_leave_the_transaction_to_retry() {
+    let _database = DATABASE.lock().await;
+    let server = server();
+    let keypair = Keypair::new();
+    let session = sign_in(&server.router, &keypair).await;
+    let (tx_id, encoded_tx, signature) = create_and_sign(&server.router, &session, &keypair).await;
+
+    // A failed send is reported and the row stays unsent, so it can be resubmitted.
+    server.rpc.fail_next_calls(1);
+    let (status, body) = submit(&server.router, &session, &tx_id, &encoded_tx).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{}", body);
+    let (status, body) = submit(&server.router, &session, &tx_id, &encoded_tx).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // A cycle that cannot reach the cluster changes nothing.
+    server.rpc.fail_next_calls(1);
+    track(&server).await;
+    assert_eq!(
+        status_data(&server.router, &session, &signature).await["status"],
+        "Sent"
+    );
+    track(&server).await;
+    assert_eq!(
+        status_data(&server.router, &session, &signature).await["status"],
+        "Processed"
+    );
+
+    cleanup(&server.pool, keypair.pubkey().to_string()).await;
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn simulation_failure_disables_signing_and_fails_preflight() {
+    let _database = DATABASE.lock().await;
+    let server = server();
+    let keypair = Keypair::new();
+    let session = sign_in(&server.router, &keypair).await;
+
+    server.rpc.set_simulation(RpcSimulateTransactionResult {
+        err: Some(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(0),
+        )),
+        logs: Some(vec!["Program log: vault already initialized".to_string()]),
+        accounts: None,
+        units_consumed: Some(1_000),
+        return_data: None,
+        inner_instructions: None,
+    });
+    let (status, _, modal) = send(
+        &server.router,
+        Request::get("/tx-modal?tx_type=initialize")
+            .header(header::COOKIE, &session)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", modal);
+    assert!(modal.contains("Simulation failed"), "{}", modal);
+    assert!(modal.contains(" disabled>"), "{}", modal);
+
+    let (tx_id, encoded_tx, signature) = create_and_sign(&server.router, &session, &keypair).await;
+    let (status, body) = submit(&server.router, &session, &tx_id, &encoded_tx).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(body.contains("Transaction simulation failed"), "{}", body);
+
+    // Preflight rejected it, so it was never sent.
+    let (status, _, _) = send(
+        &server.router,
+        Request::get(format!("/tx-status-data?tx_signature={}", signature))
+            .header(header::COOKIE, &session)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    cleanup(&server.pool, keypair.pubkey().to_string()).await;
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn landed_transaction_failing_on_chain_is_failed() {
+    let _database = DATABASE.lock().await;
+    let server = server();
+    let keypair = Keypair::new();
+    let owner = keypair.pubkey().to_string();
+    let session = sign_in(&server.router, &keypair).await;
+
+    server.rpc.set_fee(7_500);
+    let (tx_id, encoded_tx, signature) = create_and_sign(&server.router, &session, &keypair).await;
+    let (status, body) = submit(&server.router, &session, &tx_id, &encoded_tx).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    server.rpc.fail_transaction(
+        &Signature::from_str(&signature).unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(0)),
+    );
+
+    // Processed results can still be dropped from a fork, so the failure
+    // is only recorded once confirmed.
+    server.rpc.advance_block_height(1);
+    track(&server).await;
+    let data = status_data(&server.router, &session, &signature).await;
+    assert_eq!(data["status"], "Failed");
+    assert!(data["error"].is_string(), "{}", data);
+    assert_eq!(data["failed_instruction_index"], 0);
+
+    let (status, _, body) = send(
+        &server.router,
+        Request::get("/api/history")
+            .header(header::COOKIE, &session)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let history: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(history["transactions"][0]["status"], "Failed");
+    assert_eq!(history["transactions"][0]["fee"], 7_500);
+
+    cleanup(&server.pool, owner).await;
+}